@@ -27,5 +27,113 @@ pub enum Errors {
     #[msg("This model has already submitted a prediction for this task")]
     DuplicatePrediction,
     #[msg("Total reputation of models must be greater than zero")]
-    ZeroTotalReputation
+    ZeroTotalReputation,
+    #[msg("Invalid parameters for the aggregation algorithm")]
+    InvalidAlgorithmParameters,
+    #[msg("Prediction does not match the task's prediction encoding")]
+    InvalidPrediction,
+    #[msg("Fixed-point scale exceeds the maximum supported scale")]
+    InvalidPredictionEncoding,
+    #[msg("Task schema is invalid")]
+    InvalidTaskSchema,
+    #[msg("Input data does not match the task's input schema")]
+    InvalidInputData,
+    #[msg("Prediction is outside the task's output range")]
+    PredictionOutOfRange,
+    #[msg("A submitter owed a payout was not passed in the remaining accounts")]
+    MissingPayoutAccount,
+    #[msg("Token accounts required by the task's fee mint are missing or invalid")]
+    InvalidFeeTokenAccounts,
+    #[msg("Request deadline must be after the time it is posted")]
+    InvalidDeadline,
+    #[msg("The request deadline has passed")]
+    RequestExpired,
+    #[msg("The request deadline has not passed yet")]
+    DeadlineNotReached,
+    #[msg("Signer is not allowed to submit predictions for this model")]
+    UnauthorizedSubmitter,
+    #[msg("Only the model owner can manage its operators")]
+    NotModelOwner,
+    #[msg("This model already has the maximum number of operators")]
+    TooManyOperators,
+    #[msg("Operator is not registered for this model")]
+    OperatorNotFound,
+    #[msg("Operator share must not exceed 10000 basis points")]
+    InvalidOperatorShare,
+    #[msg("The model account of a submitted prediction was not passed in the remaining accounts")]
+    MissingModelAccount,
+    #[msg("The program already holds the maximum number of trees")]
+    TooManyTrees,
+    #[msg("The request already holds the maximum number of results")]
+    TooManyResults,
+    #[msg("Input data exceeds the maximum request input size")]
+    InputTooLarge,
+    #[msg("Only the program's upgrade authority can initialize it")]
+    NotUpgradeAuthority,
+    #[msg("Signer is not the program admin")]
+    NotAdmin,
+    #[msg("Signer is not the proposed admin")]
+    NotPendingAdmin,
+    #[msg("Protocol configuration is out of bounds")]
+    InvalidConfig,
+    #[msg("The program is paused")]
+    ProgramPaused,
+    #[msg("This task is paused")]
+    TaskPaused,
+    #[msg("Only the requester can close a request")]
+    NotRequester,
+    #[msg("A task can only be closed once all its models are retired")]
+    TaskHasModels,
+    #[msg("Metadata account does not belong to the collection mint")]
+    InvalidCollectionMetadata,
+    #[msg("Signer is not the collection's update authority")]
+    NotCollectionAuthority,
+    #[msg("Signer is not the task owner")]
+    NotTaskOwner,
+    #[msg("Task settings are out of bounds")]
+    InvalidTaskSettings,
+    #[msg("Request asks for fewer predictions than the task requires")]
+    TooFewPredictions,
+    #[msg("Request fee is below the task's minimum")]
+    FeeTooLow,
+    #[msg("Model reputation is below the task's minimum")]
+    ReputationTooLow,
+    #[msg("Only the requester can pick an aggregation algorithm other than the task's default")]
+    AlgorithmOverrideNotAllowed,
+    #[msg("Name, symbol or URI exceeds the token metadata length limits")]
+    MetadataTooLong,
+    #[msg("A batch must mint between 1 and MAX_BATCH_SIZE models")]
+    InvalidBatchSize,
+    #[msg("This model is already registered to the task")]
+    ModelAlreadyRegistered,
+    #[msg("Invalid Secp256k1 instruction provided to verify instruction")]
+    InvalidSecp256k1Instruction,
+    #[msg("Missing Secp256k1 instruction provided to verify instruction")]
+    MissingSecp256k1Instruction,
+    #[msg("This task takes predictions through commit_pred and reveal_pred")]
+    CommitRevealRequired,
+    #[msg("This task does not use commit-reveal")]
+    CommitRevealDisabled,
+    #[msg("The commit window of this request has closed")]
+    CommitWindowClosed,
+    #[msg("Predictions can only be revealed once the commit window has closed")]
+    RevealWindowNotOpen,
+    #[msg("No unrevealed commit for this model on this request")]
+    CommitNotFound,
+    #[msg("Revealed prediction and salt do not match the commitment")]
+    InvalidReveal,
+    #[msg("Commits are still waiting to be revealed, aggregate once they are or once the reveal period ends at the deadline")]
+    RevealWindowOpen,
+    #[msg("The request has enough predictions and can only be aggregated until the grace period is over")]
+    RequestFulfilled,
+    #[msg("Overflow occurred when updating the task's model count")]
+    ModelCountOverflow,
+    #[msg("No revealed prediction of a registered model could be decoded")]
+    NoValidPredictions,
+    #[msg("Trimming left no predictions to aggregate")]
+    EmptyTrimmedSet,
+    #[msg("The aggregated distribution sums to zero and cannot be renormalized")]
+    DistributionNotNormalizable,
+    #[msg("Overflow occurred when incrementing the request count")]
+    RequestCountOverflow
 }
@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::ProgramState;
+
+#[event]
+pub struct AdminChanged {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"knowledge"],
+        bump,
+        constraint = program_state.pending_admin == Some(pending_admin.key()) @ Errors::NotPendingAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+}
+
+pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let program_state = &mut ctx.accounts.program_state;
+    let old_admin = program_state.admin;
+    program_state.admin = ctx.accounts.pending_admin.key();
+    program_state.pending_admin = None;
+
+    emit!(AdminChanged {
+        old_admin,
+        new_admin: program_state.admin,
+    });
+
+    Ok(())
+}
@@ -1,106 +1,893 @@
 use anchor_lang::prelude::*;
-use crate::{error::Errors, state::{InferenceRequest, ModelData, ResultEntry, TaskData}};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::{error::Errors, state::{FeeEscrow, InferenceRequest, ModelData, ProtocolConfig, RequestStatus, ResultEntry, TaskData}};
+use crate::payout::{payout_weight, split_fee, split_operator_share, TokenPayout};
+use crate::prediction::{decode_vector, encode_vector, PredictionEncoding, Sample};
+use crate::schema::{OutputKind, OutputSchema};
+use crate::reputation::{penalize_unrevealed, update_reputations, ReputationChange, ReputationPolicy};
+
+#[event]
+pub struct Aggregated {
+    pub request_id: u64,
+    pub task_collection: Pubkey,
+    pub result: Vec<u8>,
+    pub num_predictions: u16,
+    pub status: RequestStatus
+}
+
+#[event]
+pub struct ReputationUpdated {
+    pub task_collection: Pubkey,
+    pub weights_hash: [u8; 32],
+    pub old_reputation: u8,
+    pub new_reputation: u8,
+    pub loss: Option<u64>,
+}
+
+#[event]
+pub struct FeePaid {
+    pub request_id: u64,
+    pub weights_hash: [u8; 32],
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ProtocolFeePaid {
+    pub request_id: u64,
+    pub treasury: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FeeRefunded {
+    pub request_id: u64,
+    pub user: Pubkey,
+    pub amount: u64,
+}
 
 #[derive(Accounts)]
-#[instruction(request_id: u16)]
+#[instruction(request_id: u64)]
 pub struct Aggregate<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
     #[account(
+        mut,
         seeds = [b"request", request_id.to_le_bytes().as_ref()],
         bump
     )]
     pub request_state: Account<'info, InferenceRequest>,
 
     #[account(
-        seeds = [b"collection123", request_state.task_collection.key().as_ref()], bump
+        seeds = [b"collection123", request_state.task_collection.key().as_ref()], bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
     )]
     pub task_data: Account<'info, TaskData>,
 
-}
+    // Closed to the requester once the fee is paid out, refunding the remainder and the rent
+    #[account(
+        mut,
+        close = requester,
+        seeds = [b"escrow", request_state.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, FeeEscrow>,
 
-pub fn aggregate(ctx: Context<Aggregate>, _request_id: u16, algorithm: AggregationAlgorithm) -> Result<()> {
-    let predictions = &ctx.accounts.request_state.results;
-    let models = &ctx.accounts.task_data.models;
-    require!(predictions.len() >= ctx.accounts.request_state.required_predictions.into(), Errors::NotEnoughPredictions);
-    match algorithm {
-        AggregationAlgorithm::WeightedMedian => {
-            let aggregate_pred = weighted_median(predictions, models);
-        },
-    }
-    Ok(())
+    #[account(mut, address = request_state.user)]
+    pub requester: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: receives the protocol fee, checked against the configured treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    // Token accounts, only required when the fee was escrowed in an SPL mint
+    #[account(mut)]
+    pub escrow_token_account: Option<Account<'info, TokenAccount>>,
+    pub fee_mint: Option<Account<'info, Mint>>,
+    /// CHECK: requester's associated token account, checked against the derived address and created if missing
+    #[account(mut)]
+    pub requester_token_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: treasury's associated token account, checked against the derived address and created if missing
+    #[account(mut)]
+    pub treasury_token_account: Option<UncheckedAccount<'info>>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+
+    // Remaining accounts, writable and in any order: the model account of every submitted prediction,
+    // closed for retired models, and every model owner and operator owed a share of the fee. For SPL fees, each recipient's wallet and its associated token account, which is created
+    // at the caller's expense if it does not exist yet
 }
 
-// Prediction must be exactly 4 bytes
-fn weighted_median(predictions: &Vec<ResultEntry>, models: &Vec<ModelData>) -> Option<f32> {
-    let mut total_reputation: u32 = 0;
+pub fn aggregate<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Aggregate<'info>>,
+    _request_id: u64,
+    algorithm: Option<AggregationAlgorithm>
+) -> Result<()> {
+    let request_state = &mut ctx.accounts.request_state;
+    let task_data = &ctx.accounts.task_data;
+    require_eq!(request_state.status.clone(), RequestStatus::Pending, Errors::InvalidStatus);
 
-    let mut prediction_reputation_map: Vec<(f32, u32)> = predictions
+    // Commits still waiting to be revealed hold aggregation back until the reveal period ends at the deadline.
+    // From then on the revealed results are aggregated without them, the unrevealed ones penalized, and
+    // expire_request refuses the request as long as enough predictions were revealed
+    let (revealed, unrevealed): (Vec<ResultEntry>, Vec<ResultEntry>) = request_state.results
         .iter()
-        .filter_map(|result| {
-            models
-                .iter()
-                .find(|model| model.weights_hash == result.weights_hash)
-                .and_then(|model| {
-                    let prediction_bytes: [u8; 4] = result.prediction
-                        .get(0..4) // Take the first 4 bytes
-                        .and_then(|slice| slice.try_into().ok()) // Convert to [u8; 4]
-                        ?;
+        .cloned()
+        .partition(ResultEntry::is_revealed);
+    require!(
+        unrevealed.is_empty() || request_state.is_reveal_period_over(Clock::get()?.unix_timestamp),
+        Errors::RevealWindowOpen
+    );
+    require!(revealed.len() >= request_state.required_predictions.into(), Errors::NotEnoughPredictions);
+
+    // The task's default algorithm applies unless the requester asks for another one
+    let algorithm = match algorithm {
+        Some(algorithm) => {
+            require_keys_eq!(ctx.accounts.user.key(), request_state.user, Errors::AlgorithmOverrideNotAllowed);
+            algorithm
+        }
+        None => task_data.settings.algorithm.clone(),
+    };
+    let output = task_data.schema.output;
+    algorithm.validate(&output.kind)?;
+    // Reputations are always scored by the task's own policy
+    let reputation_policy = task_data.settings.reputation_policy;
+
+    let mut model_accounts = load_models(ctx.remaining_accounts, request_state)?;
+    let mut models: Vec<ModelData> = model_accounts.iter().map(|account| (**account).clone()).collect();
+
+    // Fixed-point tasks are aggregated and scored with integer arithmetic only
+    let (result, mut changes) = match output.encoding {
+        PredictionEncoding::Float32 => aggregate_and_score::<f32>(
+            &algorithm, &reputation_policy, &revealed, &mut models, &output
+        ),
+        PredictionEncoding::FixedPoint { .. } => aggregate_and_score::<i64>(
+            &algorithm, &reputation_policy, &revealed, &mut models, &output
+        ),
+    }?;
+    changes.extend(penalize_unrevealed(&reputation_policy, &unrevealed, &mut models));
 
-                    // Convert bytes to f32
-                    let prediction_value = f32::from_be_bytes(prediction_bytes);
+    // Persist the updated reputations to the model accounts
+    for (account, model) in model_accounts.iter_mut().zip(&models) {
+        account.reputation = model.reputation;
+        account.exit(&crate::ID)?;
+    }
+
+    // The aggregate is stored with the same encoding as the submitted predictions
+    request_state.result = Some(result.clone());
+    request_state.status = RequestStatus::Aggregated;
 
-                    // Update the total reputation dynamically
-                    total_reputation += model.reputation as u32;
+    emit!(Aggregated {
+        request_id: request_state.request_id,
+        task_collection: request_state.task_collection,
+        result,
+        num_predictions: revealed.len() as u16,
+        status: RequestStatus::Aggregated
+    });
 
-                    Some((prediction_value, model.reputation as u32)) // Store raw reputation temporarily
-                })
+    // Split the fee by the reputation each model was aggregated with and how accurate it was
+    let weights: Vec<u64> = revealed
+        .iter()
+        .map(|result| {
+            changes
+                .iter()
+                .find(|change| change.weights_hash == result.weights_hash)
+                .map_or(0, |change| payout_weight(change.old_reputation, change.outcome))
         })
         .collect();
+    // The protocol fee comes off the top, the rest is split between the models
+    let escrow = &ctx.accounts.escrow;
+    let (shares, remainder) = split_fee(escrow.amount - escrow.protocol_fee, &weights);
+
+    let token_payout = TokenPayout::for_escrow(
+        escrow,
+        ctx.accounts.escrow_token_account.as_ref(),
+        ctx.accounts.fee_mint.as_ref(),
+        ctx.accounts.token_program.as_ref(),
+        ctx.accounts.associated_token_program.as_ref(),
+        &ctx.accounts.user,
+        &ctx.accounts.system_program,
+    )?;
+    let request_key = request_state.key();
+    let escrow_seeds: &[&[u8]] = &[b"escrow", request_key.as_ref(), &[ctx.bumps.escrow]];
+
+    if escrow.protocol_fee > 0 {
+        let treasury = ctx.accounts.treasury.to_account_info();
+        match &token_payout {
+            None => {
+                **escrow.to_account_info().try_borrow_mut_lamports()? -= escrow.protocol_fee;
+                **treasury.try_borrow_mut_lamports()? += escrow.protocol_fee;
+            }
+            Some(token_payout) => {
+                let treasury_token_account = ctx.accounts.treasury_token_account
+                    .as_ref()
+                    .ok_or(error!(Errors::InvalidFeeTokenAccounts))?
+                    .to_account_info();
+                token_payout.pay(&treasury, &treasury_token_account, escrow.protocol_fee, escrow_seeds)?;
+            }
+        }
+
+        emit!(ProtocolFeePaid {
+            request_id: request_state.request_id,
+            treasury: treasury.key(),
+            amount: escrow.protocol_fee,
+        });
+    }
+
+    for (result, share) in revealed.iter().zip(shares) {
+        if share == 0 {
+            continue;
+        }
+        // An operator that submitted on the owner's behalf receives the model's operator share
+        let model = models
+            .iter()
+            .find(|model| model.weights_hash == result.weights_hash)
+            .ok_or(error!(Errors::ModelNotFound))?;
+        let (owner_amount, operator_amount) = if result.submitter == model.owner {
+            (share, 0)
+        } else {
+            split_operator_share(share, model.operator_share_bps)
+        };
+
+        for (recipient_key, amount) in [(model.owner, owner_amount), (result.submitter, operator_amount)] {
+            if amount == 0 {
+                continue;
+            }
+            let recipient = ctx.remaining_accounts
+                .iter()
+                .find(|account| account.key() == recipient_key)
+                .ok_or(error!(Errors::MissingPayoutAccount))?;
+            match &token_payout {
+                None => {
+                    **escrow.to_account_info().try_borrow_mut_lamports()? -= amount;
+                    **recipient.try_borrow_mut_lamports()? += amount;
+                }
+                Some(token_payout) => {
+                    let recipient_token_account = token_payout.find_token_account(ctx.remaining_accounts, recipient)?;
+                    token_payout.pay(recipient, recipient_token_account, amount, escrow_seeds)?;
+                }
+            }
 
-    msg!("Total reputation: {}", total_reputation);
-    for (prediction, reputation) in &prediction_reputation_map {
-        msg!("Prediction: {}, Reputation: {}", prediction, reputation);
+            emit!(FeePaid {
+                request_id: request_state.request_id,
+                weights_hash: result.weights_hash,
+                recipient: recipient_key,
+                amount,
+            });
+        }
+    }
+
+    // The remainder goes back to the requester, and the emptied token account's rent along with the escrow's
+    let refunded = match &token_payout {
+        None => remainder,
+        Some(token_payout) => token_payout.refund_and_close(
+            &ctx.accounts.requester.to_account_info(),
+            ctx.accounts.requester_token_account.as_ref().map(|account| account.as_ref()),
+            escrow_seeds
+        )?,
+    };
+
+    emit!(FeeRefunded {
+        request_id: request_state.request_id,
+        user: request_state.user,
+        amount: refunded,
+    });
+
+    for change in changes {
+        emit!(ReputationUpdated {
+            task_collection: task_data.collection_mint,
+            weights_hash: change.weights_hash,
+            old_reputation: change.old_reputation,
+            new_reputation: change.new_reputation,
+            loss: change.loss,
+        });
+    }
+
+    Ok(())
+}
+
+// Loads the model account of every submitted prediction from the remaining accounts, once each.
+// Predictions of retired models are skipped and earn nothing.
+// Only this program can create ModelData accounts, so a deserialized account is a registered model.
+fn load_models<'info>(accounts: &'info [AccountInfo<'info>], request: &InferenceRequest) -> Result<Vec<Account<'info, ModelData>>> {
+    let mut models: Vec<Account<'info, ModelData>> = Vec::with_capacity(request.results.len());
+    for account in accounts.iter().filter(|account| account.owner == &crate::ID) {
+        let Ok(model) = Account::<ModelData>::try_from(account) else {
+            continue;
+        };
+        let submitted = request.results.iter().any(|result| result.weights_hash == model.weights_hash);
+        let loaded = models.iter().any(|loaded| loaded.weights_hash == model.weights_hash);
+        if model.task_collection == request.task_collection && submitted && !loaded {
+            models.push(model);
+        }
     }
 
-    // Normalize reputations such that the sum of normalized predictions is 1
+    // Every prediction is scored, so a model can only be left out if it was retired, which is shown
+    // by passing its closed model account
+    for result in &request.results {
+        if models.iter().any(|model| model.weights_hash == result.weights_hash) {
+            continue;
+        }
+        let (address, _) = Pubkey::find_program_address(
+            &[b"model", request.task_collection.as_ref(), result.weights_hash.as_ref()],
+            &crate::ID
+        );
+        let retired = accounts
+            .iter()
+            .any(|account| account.key() == address && account.data_is_empty());
+        require!(retired, Errors::MissingModelAccount);
+    }
+    Ok(models)
+}
+
+// Aggregates the predictions, then scores each contributing model against the aggregate.
+// Returns the encoded aggregate, or the reason nothing could be aggregated
+fn aggregate_and_score<T: Sample>(
+    algorithm: &AggregationAlgorithm,
+    policy: &ReputationPolicy,
+    predictions: &[ResultEntry],
+    models: &mut [ModelData],
+    output: &OutputSchema
+) -> Result<(Vec<u8>, Vec<ReputationChange>)> {
+    let len = output.kind.len();
+    let scale = output.encoding.scale();
+
+    // Predictions of retired models and ones that do not decode are left out of the aggregate
+    let pairs = prediction_vector_pairs::<T>(predictions, models, len);
+    require!(!pairs.is_empty(), Errors::NoValidPredictions);
+    require!(pairs.iter().any(|(_, reputation)| *reputation > 0), Errors::ZeroTotalReputation);
+
+    // With a positive total reputation, the reputation-weighted algorithms always find an aggregate
+    let aggregate_pred: Vec<T> = match algorithm {
+        AggregationAlgorithm::WeightedMedian => vec![
+            weighted_median(predictions, models).ok_or(error!(Errors::ZeroTotalReputation))?
+        ],
+        AggregationAlgorithm::WeightedMean => vec![
+            weighted_mean(predictions, models).ok_or(error!(Errors::ZeroTotalReputation))?
+        ],
+        AggregationAlgorithm::TrimmedMean { trim_bps } => vec![
+            trimmed_mean(predictions, models, *trim_bps).ok_or(error!(Errors::EmptyTrimmedSet))?
+        ],
+        AggregationAlgorithm::WeightedMode => vec![
+            weighted_mode(predictions, models).ok_or(error!(Errors::ZeroTotalReputation))?
+        ],
+        AggregationAlgorithm::CoordinateWiseMedian => coordinate_wise_median(predictions, models, len)
+            .ok_or(error!(Errors::ZeroTotalReputation))?,
+        AggregationAlgorithm::DistributionMean => distribution_mean(predictions, models, len, scale)
+            .ok_or(error!(Errors::DistributionNotNormalizable))?,
+        AggregationAlgorithm::GeometricMedian => T::geometric_median(&pairs)
+            .ok_or(error!(Errors::ZeroTotalReputation))?,
+    };
+
+    let changes = update_reputations(policy, predictions, models, &aggregate_pred, scale);
+    Ok((encode_vector(&aggregate_pred), changes))
+}
+
+// Pairs each decodable prediction with the reputation of the registered model that submitted it
+fn prediction_reputation_pairs<T: Sample>(predictions: &[ResultEntry], models: &[ModelData]) -> Vec<(T, u32)> {
+    predictions
+        .iter()
+        .filter_map(|result| {
+            let model = models
+                .iter()
+                .find(|model| model.weights_hash == result.weights_hash)?;
+            let prediction_value = T::decode(&result.prediction)?;
+            Some((prediction_value, model.reputation as u32))
+        })
+        .collect()
+}
+
+fn weighted_median<T: Sample>(predictions: &[ResultEntry], models: &[ModelData]) -> Option<T> {
+    let pairs = prediction_reputation_pairs(predictions, models);
+    weighted_median_of_pairs(pairs)
+}
+
+// Sorts by value and returns the first value at which the accumulated reputation reaches half of
+// the total. When the cumulative weight lands exactly on 50% the lower of the two candidates is
+// returned, so the result is always one of the submitted predictions and independent of input order.
+fn weighted_median_of_pairs<T: Sample>(mut pairs: Vec<(T, u32)>) -> Option<T> {
+    let total_reputation: u64 = pairs.iter().map(|(_, reputation)| *reputation as u64).sum();
     if total_reputation == 0 {
         return None
     }
 
-    let normalized_prediction_reputation_map: Vec<(f32, f32)> = prediction_reputation_map
-        .iter()
-        .map(|(prediction, reputation)| (*prediction, *reputation as f32 / total_reputation as f32))
-        .collect();
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
 
-    for (prediction, reputation) in &normalized_prediction_reputation_map {
-        msg!("Prediction: {}, Reputation: {}", prediction, reputation);
+    let mut cumulative_reputation: u64 = 0;
+    for (prediction, reputation) in pairs {
+        cumulative_reputation += reputation as u64;
+        if cumulative_reputation * 2 >= total_reputation {
+            msg!("Returning aggregated prediction: {:?}", prediction);
+            return Some(prediction);
+        }
     }
 
-    let mut weighted_predictions: Vec<f32> = normalized_prediction_reputation_map
-    .iter()
-    .map(|(prediction, normalized_reputation)| prediction * *normalized_reputation as f32)
-    .collect();
+    None
+}
 
-    weighted_predictions.sort_by(|a, b| {
-        a.partial_cmp(b).unwrap()
-    });
+// Reputation-weighted mean of all predictions
+fn weighted_mean<T: Sample>(predictions: &[ResultEntry], models: &[ModelData]) -> Option<T> {
+    let pairs = prediction_reputation_pairs(predictions, models);
+    T::weighted_mean(&pairs)
+}
+
+// Drops the lowest and highest `trim_bps / 10000` share of predictions (by count) and returns
+// the reputation-weighted mean of the rest
+fn trimmed_mean<T: Sample>(predictions: &[ResultEntry], models: &[ModelData], trim_bps: u16) -> Option<T> {
+    let mut pairs = prediction_reputation_pairs(predictions, models);
+    pairs.sort_by(|a: &(T, u32), b| a.0.total_cmp(&b.0));
+
+    let trim_count = pairs.len() * trim_bps as usize / 10_000;
+    if trim_count * 2 >= pairs.len() {
+        return None
+    }
 
-    let preds_sum: f32 = weighted_predictions.iter().sum();
-    let mut cumulative_pred: f32 = 0.0;
-    for prediction in weighted_predictions {
-        cumulative_pred += prediction;
-        if cumulative_pred >= preds_sum / 2.0 {
-            msg!("Returning aggregated prediction: {}", prediction * total_reputation as f32);
-            return Some(prediction * total_reputation as f32 );
+    T::weighted_mean(&pairs[trim_count..pairs.len() - trim_count])
+}
+
+// Plurality vote where each model votes for its predicted class label with its reputation.
+// Ties are broken in favour of the smallest label so every validator picks the same class.
+fn weighted_mode<T: Sample>(predictions: &[ResultEntry], models: &[ModelData]) -> Option<T> {
+    let pairs = prediction_reputation_pairs(predictions, models);
+
+    let mut votes: Vec<(T, u32)> = Vec::new();
+    for (label, reputation) in pairs {
+        match votes.iter_mut().find(|(candidate, _)| candidate.total_cmp(&label).is_eq()) {
+            Some((_, total)) => *total += reputation,
+            None => votes.push((label, reputation)),
         }
     }
 
-    None
+    votes
+        .into_iter()
+        .filter(|(_, total)| *total > 0)
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.total_cmp(&a.0)))
+        .map(|(label, _)| label)
+}
+
+// Vector counterpart of prediction_reputation_pairs for predictions with `len` elements
+fn prediction_vector_pairs<T: Sample>(predictions: &[ResultEntry], models: &[ModelData], len: usize) -> Vec<(Vec<T>, u32)> {
+    predictions
+        .iter()
+        .filter_map(|result| {
+            let model = models
+                .iter()
+                .find(|model| model.weights_hash == result.weights_hash)?;
+            let prediction_value = decode_vector(&result.prediction, len)?;
+            Some((prediction_value, model.reputation as u32))
+        })
+        .collect()
+}
+
+// Weighted median of each coordinate independently
+fn coordinate_wise_median<T: Sample>(predictions: &[ResultEntry], models: &[ModelData], len: usize) -> Option<Vec<T>> {
+    let pairs = prediction_vector_pairs(predictions, models, len);
+    (0..len)
+        .map(|coordinate| {
+            weighted_median_of_pairs(pairs.iter().map(|(prediction, reputation)| (prediction[coordinate], *reputation)).collect())
+        })
+        .collect()
+}
+
+// Reputation-weighted average of class distributions, renormalized so the result sums to one
+fn distribution_mean<T: Sample>(predictions: &[ResultEntry], models: &[ModelData], len: usize, scale: u8) -> Option<Vec<T>> {
+    let pairs = prediction_vector_pairs(predictions, models, len);
+    let means: Vec<T> = (0..len)
+        .map(|class| {
+            let column: Vec<(T, u32)> = pairs.iter().map(|(prediction, reputation)| (prediction[class], *reputation)).collect();
+            T::weighted_mean(&column)
+        })
+        .collect::<Option<_>>()?;
+    T::normalize(&means, scale)
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub enum AggregationAlgorithm {
+    // Scalar outputs
     WeightedMedian,
-}
\ No newline at end of file
+    WeightedMean,
+    TrimmedMean { trim_bps: u16 }, // share trimmed from each tail, must be below 5000
+    WeightedMode,                  // for classification tasks, predictions are class labels
+    // Vector outputs
+    CoordinateWiseMedian,
+    GeometricMedian,
+    // Distribution outputs
+    DistributionMean,
+}
+
+impl AggregationAlgorithm {
+    pub fn supports(&self, kind: &OutputKind) -> bool {
+        match self {
+            AggregationAlgorithm::WeightedMedian
+            | AggregationAlgorithm::WeightedMean
+            | AggregationAlgorithm::TrimmedMean { .. }
+            | AggregationAlgorithm::WeightedMode => matches!(kind, OutputKind::Scalar),
+            AggregationAlgorithm::CoordinateWiseMedian
+            | AggregationAlgorithm::GeometricMedian => matches!(kind, OutputKind::Vector { .. }),
+            AggregationAlgorithm::DistributionMean => matches!(kind, OutputKind::Distribution { .. }),
+        }
+    }
+
+    pub fn validate(&self, kind: &OutputKind) -> Result<()> {
+        if let AggregationAlgorithm::TrimmedMean { trim_bps } = self {
+            require!(*trim_bps < 5_000, Errors::InvalidAlgorithmParameters);
+        }
+        require!(self.supports(kind), Errors::InvalidAlgorithmParameters);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::state::fixtures::{encoded_result, model, result};
+    use crate::state::AGGREGATION_GRACE_PERIOD;
+
+    // Reference implementation: expand every prediction into `reputation` copies and take the
+    // lower median of the resulting multiset
+    fn reference_weighted_median(pairs: &[(f32, u32)]) -> Option<f32> {
+        let mut expanded: Vec<f32> = pairs
+            .iter()
+            .flat_map(|(prediction, reputation)| std::iter::repeat_n(*prediction, *reputation as usize))
+            .collect();
+        if expanded.is_empty() {
+            return None
+        }
+        expanded.sort_by(|a, b| a.total_cmp(b));
+        Some(expanded[(expanded.len() - 1) / 2])
+    }
+
+    #[test]
+    fn test_weighted_median() {
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![result(1, 14.25), result(2, 12.25), result(3, 13.25)];
+        assert_eq!(weighted_median(&results, &models), Some(13.25));
+
+        // A heavy model pulls the median to its own prediction
+        let models = vec![model(1, 1), model(2, 1), model(3, 5)];
+        assert_eq!(weighted_median(&results, &models), Some(13.25));
+        let models = vec![model(1, 5), model(2, 1), model(3, 1)];
+        assert_eq!(weighted_median(&results, &models), Some(14.25));
+
+        // Negative predictions are handled like any other value
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![result(1, -3.0), result(2, -1.0), result(3, -2.0)];
+        assert_eq!(weighted_median(&results, &models), Some(-2.0));
+    }
+
+    #[test]
+    fn test_weighted_median_exact_half_takes_lower_value() {
+        let models = vec![model(1, 2), model(2, 2)];
+        let results = vec![result(1, 10.0), result(2, 20.0)];
+        assert_eq!(weighted_median(&results, &models), Some(10.0));
+
+        let reversed = vec![result(2, 20.0), result(1, 10.0)];
+        assert_eq!(weighted_median(&reversed, &models), Some(10.0));
+    }
+
+    #[test]
+    fn test_weighted_median_skips_invalid_entries() {
+        let models = vec![model(1, 1), model(2, 0), model(3, 1)];
+        let results = vec![
+            result(1, 1.0),
+            result(2, -100.0), // zero reputation
+            result(3, 3.0),
+            result(4, 100.0), // unregistered
+            result(1, f32::NAN),
+        ];
+        assert_eq!(weighted_median(&results, &models), Some(1.0));
+        assert_eq!(weighted_median::<f32>(&results[1..2], &models), None);
+        assert_eq!(weighted_median::<f32>(&[], &models), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_weighted_median_matches_reference(
+            pairs in prop::collection::vec((-1.0e6f32..1.0e6f32, 0u32..=255), 1..32)
+        ) {
+            prop_assert_eq!(weighted_median_of_pairs(pairs.clone()), reference_weighted_median(&pairs));
+        }
+
+        #[test]
+        fn prop_weighted_median_is_order_independent(
+            pairs in prop::collection::vec((-1.0e6f32..1.0e6f32, 0u32..=255), 1..32),
+            rotation in 0usize..32
+        ) {
+            let mut rotated = pairs.clone();
+            let len = rotated.len();
+            rotated.rotate_left(rotation % len);
+            prop_assert_eq!(weighted_median_of_pairs(pairs), weighted_median_of_pairs(rotated));
+        }
+
+        #[test]
+        fn prop_weighted_median_is_a_submitted_prediction(
+            pairs in prop::collection::vec((-1.0e6f32..1.0e6f32, 1u32..=255), 1..32)
+        ) {
+            let median = weighted_median_of_pairs(pairs.clone()).unwrap();
+            prop_assert!(pairs.iter().any(|(prediction, _)| *prediction == median));
+        }
+    }
+
+    #[test]
+    fn test_weighted_mean() {
+        let models = vec![model(1, 1), model(2, 1), model(3, 2)];
+        let results = vec![result(1, 1.0), result(2, 2.0), result(3, 4.0)];
+
+        // (1 * 1 + 2 * 1 + 4 * 2) / 4
+        assert_eq!(weighted_mean(&results, &models), Some(2.75));
+    }
+
+    #[test]
+    fn test_weighted_mean_ignores_unregistered_and_zero_reputation() {
+        let models = vec![model(1, 3), model(2, 0)];
+        let results = vec![result(1, 5.0), result(2, 100.0), result(9, -100.0)];
+
+        assert_eq!(weighted_mean(&results, &models), Some(5.0));
+        assert_eq!(weighted_mean::<f32>(&results[1..], &models), None);
+        assert_eq!(weighted_mean::<f32>(&[], &models), None);
+    }
+
+    #[test]
+    fn test_trimmed_mean() {
+        let models: Vec<ModelData> = (1..=5).map(|id| model(id, 1)).collect();
+        let results = vec![
+            result(1, -50.0), // outlier
+            result(2, 2.0),
+            result(3, 3.0),
+            result(4, 4.0),
+            result(5, 1000.0), // outlier
+        ];
+
+        // 20% of 5 predictions trims one from each tail
+        assert_eq!(trimmed_mean(&results, &models, 2000), Some(3.0));
+
+        // Without trimming it is the plain weighted mean
+        assert_eq!(trimmed_mean::<f32>(&results, &models, 0), weighted_mean(&results, &models));
+
+        // 40% trims two from each tail, leaving only the middle prediction
+        assert_eq!(trimmed_mean(&results, &models, 4000), Some(3.0));
+
+        // Trimming everything leaves nothing to average
+        assert_eq!(trimmed_mean::<f32>(&results[..2], &models, 5000), None);
+    }
+
+    #[test]
+    fn test_weighted_mode() {
+        let models = vec![model(1, 1), model(2, 1), model(3, 3)];
+
+        // A single high reputation model outvotes two low reputation models
+        let results = vec![result(1, 0.0), result(2, 0.0), result(3, 1.0)];
+        assert_eq!(weighted_mode(&results, &models), Some(1.0));
+
+        // Equal weights agree on the plurality label
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![result(1, 2.0), result(2, 2.0), result(3, 1.0)];
+        assert_eq!(weighted_mode(&results, &models), Some(2.0));
+    }
+
+    #[test]
+    fn test_weighted_mode_tie_breaks_on_smallest_label() {
+        let models = vec![model(1, 2), model(2, 2)];
+        let results = vec![result(1, 7.0), result(2, 3.0)];
+
+        assert_eq!(weighted_mode(&results, &models), Some(3.0));
+        assert_eq!(weighted_mode(&[result(2, 3.0), result(1, 7.0)], &models), Some(3.0));
+    }
+
+    #[test]
+    fn test_fixed_point_aggregation() {
+        let fixed = |id: u8, prediction: i64| encoded_result(id, prediction.to_be_bytes().to_vec());
+        let models = vec![model(1, 1), model(2, 1), model(3, 2)];
+        let results = vec![fixed(1, 100), fixed(2, 201), fixed(3, 400)];
+
+        assert_eq!(weighted_median::<i64>(&results, &models), Some(201));
+        // (100 + 201 + 800) / 4 = 275.25, rounded down
+        assert_eq!(weighted_mean::<i64>(&results, &models), Some(275));
+        assert_eq!(weighted_mode::<i64>(&results, &models), Some(400));
+
+        // Float predictions are not valid fixed-point predictions and vice versa
+        assert_eq!(weighted_median::<i64>(&[result(1, 1.0)], &models), None);
+        assert_eq!(weighted_median::<f32>(&results, &models), None);
+    }
+
+    #[test]
+    fn test_aggregate_and_score_encodes_result() {
+        let policy = ReputationPolicy {
+            loss: crate::reputation::LossFunction::AbsoluteError,
+            tolerance: 0,
+            penalty_threshold: 0,
+            reward: 1,
+            penalty: 1,
+        };
+        let mut models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![result(1, 1.0), result(2, 2.0), result(3, 3.0)];
+
+        let output = OutputSchema { kind: OutputKind::Scalar, encoding: PredictionEncoding::Float32, range: None };
+        let (encoded, changes) = aggregate_and_score::<f32>(
+            &AggregationAlgorithm::WeightedMedian, &policy, &results, &mut models, &output
+        ).unwrap();
+
+        assert_eq!(encoded, 2.0f32.to_be_bytes().to_vec());
+        assert_eq!(changes.len(), 3);
+        assert_eq!(models.iter().map(|model| model.reputation).collect::<Vec<_>>(), vec![0, 2, 0]);
+    }
+
+    #[test]
+    fn test_aggregate_and_score_errors() {
+        let policy = ReputationPolicy {
+            loss: crate::reputation::LossFunction::AbsoluteError,
+            tolerance: 0,
+            penalty_threshold: 0,
+            reward: 1,
+            penalty: 1,
+        };
+        let output = OutputSchema { kind: OutputKind::Scalar, encoding: PredictionEncoding::Float32, range: None };
+        let results = vec![result(1, 1.0), result(2, 2.0)];
+
+        // Every submitter was retired
+        let error = aggregate_and_score::<f32>(&AggregationAlgorithm::WeightedMedian, &policy, &results, &mut [], &output)
+            .map(|_| ());
+        assert_eq!(error, Err(error!(Errors::NoValidPredictions)));
+
+        // Every submitter is at zero reputation
+        let mut models = vec![model(1, 0), model(2, 0)];
+        let error = aggregate_and_score::<f32>(&AggregationAlgorithm::WeightedMean, &policy, &results, &mut models, &output)
+            .map(|_| ());
+        assert_eq!(error, Err(error!(Errors::ZeroTotalReputation)));
+
+        // Means that round down to zero everywhere cannot be renormalized
+        let output = OutputSchema {
+            kind: OutputKind::Distribution { classes: 3 },
+            encoding: PredictionEncoding::FixedPoint { scale: 0 },
+            range: None,
+        };
+        let results = vec![
+            vector_result(1, &[1i64, 0, 0]),
+            vector_result(2, &[0i64, 1, 0]),
+            vector_result(3, &[0i64, 0, 1]),
+        ];
+        let mut models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let error = aggregate_and_score::<i64>(&AggregationAlgorithm::DistributionMean, &policy, &results, &mut models, &output)
+            .map(|_| ());
+        assert_eq!(error, Err(error!(Errors::DistributionNotNormalizable)));
+    }
+
+    #[test]
+    fn test_zero_reputation_request_expires_after_grace_period() {
+        let policy = ReputationPolicy {
+            loss: crate::reputation::LossFunction::AbsoluteError,
+            tolerance: 0,
+            penalty_threshold: 0,
+            reward: 1,
+            penalty: 1,
+        };
+        let output = OutputSchema { kind: OutputKind::Scalar, encoding: PredictionEncoding::Float32, range: None };
+        let request = InferenceRequest {
+            request_id: 0,
+            user: Pubkey::new_unique(),
+            task_collection: Pubkey::default(),
+            input_data: vec![],
+            posted_at: 100,
+            deadline: 200,
+            commit_deadline: None,
+            required_predictions: 2,
+            status: RequestStatus::Pending,
+            results: vec![result(1, 1.0), result(2, 2.0)],
+            result: None,
+        };
+
+        // Every submitter has been penalized down to zero, so aggregate can never settle the request
+        let mut models = vec![model(1, 0), model(2, 0)];
+        let error = aggregate_and_score::<f32>(&AggregationAlgorithm::WeightedMedian, &policy, &request.results, &mut models, &output)
+            .map(|_| ());
+        assert_eq!(error, Err(error!(Errors::ZeroTotalReputation)));
+
+        // It is kept for aggregate past the deadline, then refunds once the grace period is over
+        assert!(request.has_enough_predictions());
+        assert!(!request.can_expire(request.deadline + 1));
+        assert!(request.can_expire(request.deadline + AGGREGATION_GRACE_PERIOD + 1));
+    }
+
+    fn vector_result<T: Sample>(id: u8, prediction: &[T]) -> ResultEntry {
+        encoded_result(id, encode_vector(prediction))
+    }
+
+    #[test]
+    fn test_coordinate_wise_median() {
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![
+            vector_result(1, &[1.0f32, 30.0]),
+            vector_result(2, &[2.0f32, 10.0]),
+            vector_result(3, &[3.0f32, 20.0]),
+            vector_result(1, &[100.0f32]), // wrong length, ignored
+        ];
+
+        assert_eq!(coordinate_wise_median::<f32>(&results, &models, 2), Some(vec![2.0, 20.0]));
+    }
+
+    #[test]
+    fn test_distribution_mean() {
+        let models = vec![model(1, 1), model(2, 3)];
+        let results = vec![
+            vector_result(1, &[10_000i64, 0, 0]),
+            vector_result(2, &[0i64, 5_000, 5_000]),
+        ];
+
+        // (1 * [1, 0, 0] + 3 * [0, 0.5, 0.5]) / 4 at scale 4
+        assert_eq!(distribution_mean::<i64>(&results, &models, 3, 4), Some(vec![2_500, 3_750, 3_750]));
+
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![
+            vector_result(1, &[1i64, 0, 0]),
+            vector_result(2, &[0i64, 1, 0]),
+            vector_result(3, &[0i64, 0, 1]),
+        ];
+        // Means that round down to zero everywhere cannot be renormalized
+        assert_eq!(distribution_mean::<i64>(&results, &models, 3, 0), None);
+
+        // Means of 33 are renormalized to sum to exactly one
+        let results = vec![
+            vector_result(1, &[100i64, 0, 0]),
+            vector_result(2, &[0i64, 100, 0]),
+            vector_result(3, &[0i64, 0, 100]),
+        ];
+        assert_eq!(distribution_mean::<i64>(&results, &models, 3, 2), Some(vec![34, 33, 33]));
+    }
+
+    #[test]
+    fn test_geometric_median_aggregation() {
+        let models = vec![model(1, 1), model(2, 1), model(3, 1)];
+        let results = vec![
+            vector_result(1, &[0i64, 0]),
+            vector_result(2, &[10i64, 0]),
+            vector_result(3, &[1_000i64, 0]),
+        ];
+        let output = OutputSchema {
+            kind: OutputKind::Vector { len: 2 },
+            encoding: PredictionEncoding::FixedPoint { scale: 0 },
+            range: None,
+        };
+        let policy = ReputationPolicy {
+            loss: crate::reputation::LossFunction::AbsoluteError,
+            tolerance: 0,
+            penalty_threshold: 100 * crate::prediction::LOSS_SCALE,
+            reward: 1,
+            penalty: 1,
+        };
+        let mut models = models;
+
+        let (encoded, _) = aggregate_and_score::<i64>(
+            &AggregationAlgorithm::GeometricMedian, &policy, &results, &mut models, &output
+        ).unwrap();
+
+        // Collinear points, the geometric median is the middle point
+        assert_eq!(decode_vector::<i64>(&encoded, 2), Some(vec![10, 0]));
+        assert_eq!(models.iter().map(|model| model.reputation).collect::<Vec<_>>(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_algorithm_supports_output_kind() {
+        let scalar = OutputKind::Scalar;
+        let vector = OutputKind::Vector { len: 4 };
+        let distribution = OutputKind::Distribution { classes: 4 };
+
+        assert!(AggregationAlgorithm::WeightedMedian.supports(&scalar));
+        assert!(!AggregationAlgorithm::WeightedMedian.supports(&vector));
+        assert!(AggregationAlgorithm::GeometricMedian.supports(&vector));
+        assert!(!AggregationAlgorithm::GeometricMedian.supports(&distribution));
+        assert!(AggregationAlgorithm::DistributionMean.supports(&distribution));
+        assert!(!AggregationAlgorithm::DistributionMean.supports(&scalar));
+    }
+}
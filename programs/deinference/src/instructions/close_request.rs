@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::InferenceRequest;
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct CloseRequest<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    // Pending requests are closed through aggregate or expire_request, which settle the fee first
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"request", request_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = user @ Errors::NotRequester,
+        constraint = request_state.status.is_final() @ Errors::InvalidStatus
+    )]
+    pub request_state: Account<'info, InferenceRequest>,
+}
+
+pub fn close_request(_ctx: Context<CloseRequest>, _request_id: u64) -> Result<()> {
+    Ok(())
+}
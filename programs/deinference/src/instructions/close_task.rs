@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::TaskData;
+
+#[derive(Accounts)]
+pub struct CloseTask<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    // Models hold their task's key, so they are retired before the task goes away.
+    // The owner paid the rent when creating the task and gets it back
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump,
+        has_one = owner @ Errors::NotTaskOwner,
+        constraint = task_data.model_count == 0 @ Errors::TaskHasModels
+    )]
+    pub task_data: Account<'info, TaskData>,
+}
+
+pub fn close_task(_ctx: Context<CloseTask>) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anchor_lang::prelude::*;
+use crate::state::{InferenceRequest, ModelData, ProtocolConfig, RequestStatus, ResultEntry, TaskData};
+use crate::error::Errors;
+use crate::instructions::authorize_submitter;
+use crate::verify::prediction_message;
+
+#[derive(Accounts)]
+#[instruction(request_id: u64, weights_hash: [u8; 32])]
+pub struct CommitPred<'info> {
+    #[account(mut)]
+    pub model_owner: Signer<'info>,
+
+    // Grown by one result, sized for the prediction it will be revealed with, the submitter funds the extra rent
+    #[account(
+        mut,
+        seeds = [b"request", request_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = request_state.results.len() < config.max_results.into() @ Errors::TooManyResults,
+        realloc = InferenceRequest::space(
+            request_state.input_data.len(),
+            request_state.results.len() + 1,
+            task_data.schema.output.prediction_size()
+        ),
+        realloc::payer = model_owner,
+        realloc::zero = false
+    )]
+    pub request_state: Account<'info, InferenceRequest>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
+    )]
+    pub task_data: Account<'info, TaskData>,
+
+    #[account(
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump
+    )]
+    pub model_data: Account<'info, ModelData>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Sysvar account for instruction introspection, read for commitments attested by an Ethereum identity
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instruction_sysvar: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Stores ResultEntry::commitment_hash of a prediction during the commit window, reveal_pred discloses it afterwards
+pub fn commit_pred(ctx: Context<CommitPred>, request_id: u64, weights_hash: [u8; 32], commitment: [u8; 32]) -> Result<()> {
+    let task_data = &ctx.accounts.task_data;
+    let request_state = &mut ctx.accounts.request_state;
+
+    require_keys_eq!(task_data.collection_mint, *ctx.accounts.collection_mint.key);
+    // The model account is per task, so the request must belong to the same task
+    require_keys_eq!(request_state.task_collection, *ctx.accounts.collection_mint.key);
+    let model_data = &ctx.accounts.model_data;
+    require!(model_data.reputation >= task_data.settings.min_reputation, Errors::ReputationTooLow);
+    require_eq!(request_state.request_id, request_id);
+    require_eq!(request_state.status.clone(), RequestStatus::Pending, Errors::InvalidStatus);
+    require!(request_state.commit_deadline.is_some(), Errors::CommitRevealDisabled);
+    require!(request_state.is_commit_phase(Clock::get()?.unix_timestamp), Errors::CommitWindowClosed);
+    require!(
+        !request_state.results.iter().any(|entry| entry.weights_hash == weights_hash),
+        Errors::DuplicatePrediction
+    );
+
+    let submitter = authorize_submitter(
+        model_data,
+        ctx.accounts.model_owner.key,
+        &ctx.accounts.instruction_sysvar,
+        &prediction_message(&crate::ID, request_id, &weights_hash, &commitment)
+    )?;
+
+    request_state.results.push(ResultEntry {
+        weights_hash,
+        submitter,
+        prediction: vec![],
+        commitment: Some(commitment)
+    });
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::error::Errors;
+use crate::payout::TokenPayout;
+use crate::state::{FeeEscrow, InferenceRequest, RequestStatus};
+
+#[event]
+pub struct RequestExpired {
+    pub request_id: u64,
+    pub task_collection: Pubkey,
+    pub num_predictions: u16,
+    pub refunded: u64,
+    pub status: RequestStatus
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct ExpireRequest<'info> {
+    // Anyone can expire a request once its deadline has passed without enough predictions, or once it is left
+    // unaggregated past the grace period
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    // Closed to the requester, returning the rent they paid
+    #[account(
+        mut,
+        close = requester,
+        seeds = [b"request", request_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub request_state: Account<'info, InferenceRequest>,
+
+    // Closed to the requester, refunding the whole fee along with the rent
+    #[account(
+        mut,
+        close = requester,
+        seeds = [b"escrow", request_state.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, FeeEscrow>,
+
+    #[account(mut, address = request_state.user)]
+    pub requester: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    // Token accounts, only required when the fee was escrowed in an SPL mint
+    #[account(mut)]
+    pub escrow_token_account: Option<Account<'info, TokenAccount>>,
+    pub fee_mint: Option<Account<'info, Mint>>,
+    /// CHECK: requester's associated token account, checked against the derived address and created if missing
+    #[account(mut)]
+    pub requester_token_account: Option<UncheckedAccount<'info>>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+}
+
+pub fn expire_request(ctx: Context<ExpireRequest>, _request_id: u64) -> Result<()> {
+    let request_state = &mut ctx.accounts.request_state;
+    require_eq!(request_state.status.clone(), RequestStatus::Pending, Errors::InvalidStatus);
+    let now = Clock::get()?.unix_timestamp;
+    require!(request_state.is_expired(now), Errors::DeadlineNotReached);
+    // A request with enough predictions pays its models through aggregate rather than refunding the requester,
+    // unless aggregate still has not succeeded once the grace period is over
+    require!(request_state.can_expire(now), Errors::RequestFulfilled);
+
+    let escrow = &ctx.accounts.escrow;
+    let token_payout = TokenPayout::for_escrow(
+        escrow,
+        ctx.accounts.escrow_token_account.as_ref(),
+        ctx.accounts.fee_mint.as_ref(),
+        ctx.accounts.token_program.as_ref(),
+        ctx.accounts.associated_token_program.as_ref(),
+        &ctx.accounts.cranker,
+        &ctx.accounts.system_program,
+    )?;
+
+    // Lamport fees are refunded when the escrow is closed, token fees are returned here
+    let refunded = match token_payout {
+        None => escrow.amount,
+        Some(token_payout) => {
+            let request_key = request_state.key();
+            let escrow_seeds: &[&[u8]] = &[b"escrow", request_key.as_ref(), &[ctx.bumps.escrow]];
+            token_payout.refund_and_close(
+                &ctx.accounts.requester.to_account_info(),
+                ctx.accounts.requester_token_account.as_ref().map(|account| account.as_ref()),
+                escrow_seeds
+            )?
+        }
+    };
+
+    request_state.status = RequestStatus::Expired;
+
+    emit!(RequestExpired {
+        request_id: request_state.request_id,
+        task_collection: request_state.task_collection,
+        num_predictions: request_state.results.len() as u16,
+        refunded,
+        status: RequestStatus::Expired
+    });
+
+    Ok(())
+}
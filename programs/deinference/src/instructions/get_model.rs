@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use crate::{error::Errors, state::TaskData};
+use crate::state::ModelData;
 
 #[event]
 pub struct ModelRetrieved {
@@ -10,13 +10,13 @@ pub struct ModelRetrieved {
 }
 
 #[derive(Accounts)]
+#[instruction(weights_hash: [u8; 32])]
 pub struct GetModel<'info>{
     #[account(
-        mut,
-        seeds = [b"collection123", collection_mint.key().as_ref()],
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
         bump
     )]
-    pub task_data: Account<'info, TaskData>,
+    pub model_data: Account<'info, ModelData>,
 
     /// CHECK: unsafe
     pub collection_mint: UncheckedAccount<'info>,
@@ -25,15 +25,9 @@ pub struct GetModel<'info>{
     pub payer: Signer<'info>
 }
 
-pub fn get_model(ctx: Context<GetModel>, weights_hash: [u8; 32]) -> Result<()> {
-    let task_data = &ctx.accounts.task_data;
+pub fn get_model(ctx: Context<GetModel>, _weights_hash: [u8; 32]) -> Result<()> {
+    let model = &ctx.accounts.model_data;
 
-    let model = task_data.
-        models.
-        iter().
-        find(|model| model.weights_hash == weights_hash).
-        ok_or(error!(Errors::ModelNotFound))?;
-        
     emit!(ModelRetrieved {
         weights_hash: model.weights_hash,
         tree_address: model.tree_address,
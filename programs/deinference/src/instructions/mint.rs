@@ -1,12 +1,24 @@
 use anchor_lang::prelude::*;
 use mpl_bubblegum::{instructions::MintV1CpiBuilder, types::{MetadataArgs, TokenProgramVersion, TokenStandard}};
 use crate::{MplBubblegum, Noop, SplAccountCompression};
+use crate::error::Errors;
+use crate::state::ProgramState;
 
 #[derive(Accounts)]
 pub struct Mint<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    // Minting signs as the tree owner, so only the admin can mint outside of a task
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"knowledge"],
+        bump,
+        has_one = admin @ Errors::NotAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
     // Mut so the # of NFTs minted for this tree can be incremented
     #[account(mut)]
     /// CHECK: This account is neither written to nor read from. 
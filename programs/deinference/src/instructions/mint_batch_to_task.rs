@@ -0,0 +1,142 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::instructions::{model_metadata, validate_model_metadata, ModelMinter};
+use crate::state::{ModelIdentity, ProgramState, ProtocolConfig, TaskData};
+use crate::{MplBubblegum, Noop, SplAccountCompression, Metadata};
+use crate::verify::{verify_ed25519_instruction, weights_message};
+
+// Bounded by the transaction size well before compute, each model carries its metadata and signature twice
+pub const MAX_BATCH_SIZE: usize = 4;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ModelMint {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub weights_hash: [u8; 32],
+    pub weights_signature: [u8; 64],
+}
+
+#[derive(Accounts)]
+pub struct MintBatchToTask<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"knowledge"],
+        bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
+    )]
+    pub task_data: Account<'info, TaskData>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: This account is checked in the downstream instruction
+    #[account(mut)]
+    pub tree_auth: UncheckedAccount<'info>,
+
+    /// CHECK: This account is neither written to nor read from.
+    pub model_owner: AccountInfo<'info>,
+
+    #[account(mut)]
+    /// CHECK: unsafe
+    pub tree: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"tree_owner", tree.key().as_ref()],
+        bump
+    )]
+    /// CHECK: unsafe
+    pub collection_authority: UncheckedAccount<'info>,
+
+    /// CHECK: This account is checked in the instruction
+    pub collection_mint: UncheckedAccount<'info>,
+
+    /// CHECK: unsafe
+    #[account(mut)]
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    /// CHECK: This account is checked in the instruction
+    pub edition_account: UncheckedAccount<'info>,
+
+    /// CHECK: Sysvar account for instruction introspection
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instruction_sysvar: AccountInfo<'info>,
+
+    /// CHECK: This is just used as a signing PDA.
+    pub bubblegum_signer: UncheckedAccount<'info>,
+    pub log_wrapper: Program<'info, Noop>,
+    pub compression_program: Program<'info, SplAccountCompression>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub bubblegum_program: Program<'info, MplBubblegum>,
+    pub system_program: Program<'info, System>,
+
+    // Remaining accounts, writable and in the order of `models`: the model account of each model, created here
+}
+
+// Mints every model to the same owner, whose signatures may all be carried by a single Ed25519 instruction
+pub fn mint_batch_to_task<'info>(
+    ctx: Context<'_, '_, '_, 'info, MintBatchToTask<'info>>,
+    models: Vec<ModelMint>,
+    seller_fee_basis_points: u16
+) -> Result<()> {
+    require!(!models.is_empty() && models.len() <= MAX_BATCH_SIZE, Errors::InvalidBatchSize);
+    require_eq!(ctx.remaining_accounts.len(), models.len(), Errors::MissingModelAccount);
+
+    let collection_mint = ctx.accounts.collection_mint.key();
+    for model in &models {
+        validate_model_metadata(&model.name, &model.symbol, &model.uri)?;
+        verify_ed25519_instruction(
+            &ctx.accounts.instruction_sysvar,
+            ctx.accounts.model_owner.key.as_ref(),
+            &weights_message(&crate::ID, &collection_mint, ctx.accounts.model_owner.key, &model.weights_hash),
+            &model.weights_signature
+        )?;
+    }
+
+    let accounts = ctx.accounts;
+    let mut minter = ModelMinter {
+        payer: &accounts.payer,
+        program_state: &mut accounts.program_state,
+        initial_reputation: accounts.config.initial_reputation,
+        tree_auth: &accounts.tree_auth,
+        model_owner: &accounts.model_owner,
+        tree: &accounts.tree,
+        collection_authority: &accounts.collection_authority,
+        collection_authority_bump: ctx.bumps.collection_authority,
+        collection_mint: &accounts.collection_mint,
+        collection_metadata: &accounts.collection_metadata,
+        edition_account: &accounts.edition_account,
+        bubblegum_signer: &accounts.bubblegum_signer,
+        log_wrapper: &accounts.log_wrapper,
+        compression_program: &accounts.compression_program,
+        token_metadata_program: &accounts.token_metadata_program,
+        bubblegum_program: &accounts.bubblegum_program,
+        system_program: &accounts.system_program,
+    };
+    for (model, model_account) in models.into_iter().zip(ctx.remaining_accounts) {
+        let metadata = model_metadata(model.name, model.symbol, model.uri, seller_fee_basis_points, collection_mint);
+        minter.mint(model_account, metadata, model.weights_hash, ModelIdentity::Ed25519)?;
+    }
+
+    let task_data = &mut accounts.task_data;
+    task_data.model_count = u16::try_from(ctx.remaining_accounts.len())
+        .ok()
+        .and_then(|minted| task_data.model_count.checked_add(minted))
+        .ok_or(Errors::ModelCountOverflow)?;
+
+    Ok(())
+}
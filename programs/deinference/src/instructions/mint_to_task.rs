@@ -1,12 +1,15 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer};
 use mpl_bubblegum::instructions::MintToCollectionV1CpiBuilder;
 use mpl_bubblegum::types::{Collection, MetadataArgs, TokenProgramVersion, TokenStandard};
 use crate::error::Errors;
-use crate::state::{ModelData, ProgramState, TaskData};
+use crate::state::{ModelData, ModelIdentity, ProgramState, ProtocolConfig, TaskData};
 use crate::{MplBubblegum, Noop, SplAccountCompression, Metadata};
-use crate::verify::verify_ed25519_instruction;
+use crate::verify::{eth_signed_message, verify_ed25519_instruction, verify_secp256k1_instruction, weights_message};
+use mpl_token_metadata::{MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH};
 
 #[derive(Accounts)]
+#[instruction(name: String, symbol: String, uri: String, weights_hash: [u8; 32])]
 pub struct MintToTask<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -22,10 +25,26 @@ pub struct MintToTask<'info> {
     #[account(
         mut,
         seeds = [b"collection123", collection_mint.key().as_ref()],
-        bump
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
     )]
     pub task_data: Account<'info, TaskData>,
 
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: created by ModelMinter::mint, which fails if this model is already registered to the task
+    #[account(
+        mut,
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump
+    )]
+    pub model_data: UncheckedAccount<'info>,
+
     /// CHECK: This account is checked in the downstream instruction
     #[account(mut)]
     pub tree_auth: UncheckedAccount<'info>,
@@ -67,82 +86,252 @@ pub struct MintToTask<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn mint_to_task(ctx: Context<MintToTask>, name: String, symbol: String, weights: String, weights_signature: [u8; 64], seller_fee_basis_points: u16) -> Result<()> {
-    let weights_clone = weights.clone();
-    MintToCollectionV1CpiBuilder::new(
-        &ctx.accounts.bubblegum_program.to_account_info(),
-    )
-        .tree_config(&ctx.accounts.tree_auth.to_account_info())
-        .leaf_owner(&ctx.accounts.model_owner.to_account_info())
-        .leaf_delegate(&ctx.accounts.model_owner.to_account_info())
-        .merkle_tree(&ctx.accounts.tree.to_account_info())
-        .payer(&ctx.accounts.payer.to_account_info())
-        .tree_creator_or_delegate(&ctx.accounts.collection_authority.to_account_info())
-        .collection_authority(&ctx.accounts.collection_authority.to_account_info())
-        .collection_authority_record_pda(Some(&ctx.accounts.bubblegum_program.to_account_info()))
-        .collection_mint(&ctx.accounts.collection_mint.to_account_info())
-        .collection_metadata(&ctx.accounts.collection_metadata.to_account_info())
-        .collection_edition(&ctx.accounts.edition_account.to_account_info())
-        .bubblegum_signer(&ctx.accounts.bubblegum_signer.to_account_info())
-        .log_wrapper(&ctx.accounts.log_wrapper.to_account_info())
-        .compression_program(&ctx.accounts.compression_program.to_account_info())
-        .system_program(&ctx.accounts.system_program.to_account_info())
-        .token_metadata_program(&ctx.accounts.token_metadata_program.to_account_info())
-        .metadata(
-            MetadataArgs {
-                name,
-                symbol,
-                uri: weights,
-                creators: vec![],
-                seller_fee_basis_points,
-                primary_sale_happened: false,
-                is_mutable: false,
-                edition_nonce: Some(0),
-                uses: None,
-                collection: Some(Collection {
-                    verified: true,
-                    key: ctx.accounts.collection_mint.key(),
-                }),
-                token_program_version: TokenProgramVersion::Original,
-                token_standard: Some(TokenStandard::NonFungible),
-            }
-        )
-        .invoke_signed(&[&[
-            b"tree_owner",
-            ctx.accounts.tree.key().as_ref(),
-            &[ctx.bumps.collection_authority]
-    ]])?;
-
-    // Ensure the weights_hash are signed by the model owner
+pub fn mint_to_task(
+    ctx: Context<MintToTask>,
+    name: String,
+    symbol: String,
+    uri: String,
+    weights_hash: [u8; 32],
+    weights_signature: [u8; 64],
+    seller_fee_basis_points: u16
+) -> Result<()> {
+    validate_model_metadata(&name, &symbol, &uri)?;
+
+    // The model owner signs the weights hash for this program and task, so the signature cannot be replayed elsewhere
     verify_ed25519_instruction(
         &ctx.accounts.instruction_sysvar,
         ctx.accounts.model_owner.key.as_ref(),
-        weights_clone.as_bytes(),
+        &weights_message(&crate::ID, &ctx.accounts.collection_mint.key(), ctx.accounts.model_owner.key, &weights_hash),
         &weights_signature
     )?;
 
+    register_model(ctx, name, symbol, uri, weights_hash, ModelIdentity::Ed25519, seller_fee_basis_points)
+}
+
+// Same as mint_to_task for owners holding an Ethereum key, the Secp256k1 precompile instruction carries their signature
+// over the EIP-191 form of the weights message, which names `model_owner`. The cNFT goes to that owner, which manages the model's operators
+pub fn mint_to_task_eth(
+    ctx: Context<MintToTask>,
+    name: String,
+    symbol: String,
+    uri: String,
+    weights_hash: [u8; 32],
+    eth_address: [u8; 20],
+    seller_fee_basis_points: u16
+) -> Result<()> {
+    validate_model_metadata(&name, &symbol, &uri)?;
+
+    verify_secp256k1_instruction(
+        &ctx.accounts.instruction_sysvar,
+        &eth_address,
+        &eth_signed_message(&weights_message(&crate::ID, &ctx.accounts.collection_mint.key(), ctx.accounts.model_owner.key, &weights_hash))
+    )?;
+
+    register_model(ctx, name, symbol, uri, weights_hash, ModelIdentity::Secp256k1 { eth_address }, seller_fee_basis_points)
+}
+
+// Mints the model cNFT into the task collection and stores its ModelData, once its weights hash has been attested
+fn register_model(
+    ctx: Context<MintToTask>,
+    name: String,
+    symbol: String,
+    uri: String,
+    weights_hash: [u8; 32],
+    identity: ModelIdentity,
+    seller_fee_basis_points: u16
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    let metadata = model_metadata(name, symbol, uri, seller_fee_basis_points, accounts.collection_mint.key());
+    ModelMinter {
+        payer: &accounts.payer,
+        program_state: &mut accounts.program_state,
+        initial_reputation: accounts.config.initial_reputation,
+        tree_auth: &accounts.tree_auth,
+        model_owner: &accounts.model_owner,
+        tree: &accounts.tree,
+        collection_authority: &accounts.collection_authority,
+        collection_authority_bump: ctx.bumps.collection_authority,
+        collection_mint: &accounts.collection_mint,
+        collection_metadata: &accounts.collection_metadata,
+        edition_account: &accounts.edition_account,
+        bubblegum_signer: &accounts.bubblegum_signer,
+        log_wrapper: &accounts.log_wrapper,
+        compression_program: &accounts.compression_program,
+        token_metadata_program: &accounts.token_metadata_program,
+        bubblegum_program: &accounts.bubblegum_program,
+        system_program: &accounts.system_program,
+    }.mint(&accounts.model_data, metadata, weights_hash, identity)?;
+
     // Increment model count for this task
-    let task_data = &mut ctx.accounts.task_data;
-    task_data.model_count += 1;
-
-    // Append model to task data
-    let program_state = &mut ctx.accounts.program_state;
-    let tree = &ctx.accounts.tree;
-    let leaf_index = program_state.
-        get_tree(*tree.key).
-        ok_or(error!(Errors::TreeNotFound))?.current_index;
-    
-    let model = ModelData {
-        weights_hash: weights_clone.as_bytes().try_into().expect("URI must be 32 bytes"),
-        tree_address: *ctx.accounts.tree.key,
-        leaf_index: leaf_index,
-        reputation: 1 // inital value
-    };
-
-    task_data.models.push(model);
-
-    // Increment tree index
-    program_state.increment_index(*tree.key)?;
-    
+    let task_data = &mut accounts.task_data;
+    task_data.model_count = task_data.model_count.checked_add(1)
+        .ok_or(Errors::ModelCountOverflow)?;
+
+    Ok(())
+}
+
+// Accounts every model mint needs, borrowed from the context of mint_to_task or mint_batch_to_task
+pub(crate) struct ModelMinter<'a, 'info> {
+    pub payer: &'a AccountInfo<'info>,
+    pub program_state: &'a mut Account<'info, ProgramState>,
+    pub initial_reputation: u8,
+    pub tree_auth: &'a AccountInfo<'info>,
+    pub model_owner: &'a AccountInfo<'info>,
+    pub tree: &'a AccountInfo<'info>,
+    pub collection_authority: &'a AccountInfo<'info>,
+    pub collection_authority_bump: u8,
+    pub collection_mint: &'a AccountInfo<'info>,
+    pub collection_metadata: &'a AccountInfo<'info>,
+    pub edition_account: &'a AccountInfo<'info>,
+    pub bubblegum_signer: &'a AccountInfo<'info>,
+    pub log_wrapper: &'a AccountInfo<'info>,
+    pub compression_program: &'a AccountInfo<'info>,
+    pub token_metadata_program: &'a AccountInfo<'info>,
+    pub bubblegum_program: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> ModelMinter<'a, 'info> {
+    // Mints the model cNFT to the model owner and creates its ModelData at `model_account`, the model PDA of the task
+    // and weights hash. Fails if the model is already registered to the task
+    pub fn mint(
+        &mut self,
+        model_account: &AccountInfo<'info>,
+        metadata: MetadataArgs,
+        weights_hash: [u8; 32],
+        identity: ModelIdentity
+    ) -> Result<()> {
+        let tree = self.tree.key();
+        MintToCollectionV1CpiBuilder::new(self.bubblegum_program)
+            .tree_config(self.tree_auth)
+            .leaf_owner(self.model_owner)
+            .leaf_delegate(self.model_owner)
+            .merkle_tree(self.tree)
+            .payer(self.payer)
+            .tree_creator_or_delegate(self.collection_authority)
+            .collection_authority(self.collection_authority)
+            .collection_authority_record_pda(Some(self.bubblegum_program))
+            .collection_mint(self.collection_mint)
+            .collection_metadata(self.collection_metadata)
+            .collection_edition(self.edition_account)
+            .bubblegum_signer(self.bubblegum_signer)
+            .log_wrapper(self.log_wrapper)
+            .compression_program(self.compression_program)
+            .system_program(self.system_program)
+            .token_metadata_program(self.token_metadata_program)
+            .metadata(metadata)
+            .invoke_signed(&[&[
+                b"tree_owner",
+                tree.as_ref(),
+                &[self.collection_authority_bump]
+        ]])?;
+
+        // Store the model in its own account
+        let leaf_index = self.program_state.
+            get_tree(tree).
+            ok_or(error!(Errors::TreeNotFound))?.current_index;
+        let model_data = ModelData {
+            task_collection: self.collection_mint.key(),
+            weights_hash,
+            tree_address: tree,
+            leaf_index,
+            reputation: self.initial_reputation,
+            owner: self.model_owner.key(),
+            identity,
+            operators: vec![],
+            operator_share_bps: 0,
+        };
+        self.create_model_account(model_account, &model_data)?;
+
+        // Increment tree index
+        self.program_state.increment_index(tree)
+    }
+
+    // Creates and writes the model PDA. The batch passes model accounts as remaining accounts,
+    // so this is done here for both instructions instead of through `init`
+    fn create_model_account(&self, model_account: &AccountInfo<'info>, model_data: &ModelData) -> Result<()> {
+        let (expected, bump) = Pubkey::find_program_address(
+            &[b"model", model_data.task_collection.as_ref(), model_data.weights_hash.as_ref()],
+            &crate::ID
+        );
+        require_keys_eq!(model_account.key(), expected, Errors::MissingModelAccount);
+        require!(model_account.data_is_empty() && model_account.owner == &System::id(), Errors::ModelAlreadyRegistered);
+
+        let seeds: &[&[u8]] = &[b"model", model_data.task_collection.as_ref(), model_data.weights_hash.as_ref(), &[bump]];
+        let space = ModelData::space(0);
+        let rent = Rent::get()?.minimum_balance(space);
+        let current_lamports = model_account.lamports();
+        if current_lamports == 0 {
+            create_account(
+                CpiContext::new_with_signer(
+                    self.system_program.clone(),
+                    CreateAccount { from: self.payer.clone(), to: model_account.clone() },
+                    &[seeds]
+                ),
+                rent,
+                space as u64,
+                &crate::ID
+            )?;
+        } else {
+            // Someone sent lamports to the address ahead of time, top it up and take it over
+            if rent > current_lamports {
+                transfer(
+                    CpiContext::new(
+                        self.system_program.clone(),
+                        Transfer { from: self.payer.clone(), to: model_account.clone() }
+                    ),
+                    rent - current_lamports
+                )?;
+            }
+            allocate(
+                CpiContext::new_with_signer(
+                    self.system_program.clone(),
+                    Allocate { account_to_allocate: model_account.clone() },
+                    &[seeds]
+                ),
+                space as u64
+            )?;
+            assign(
+                CpiContext::new_with_signer(
+                    self.system_program.clone(),
+                    Assign { account_to_assign: model_account.clone() },
+                    &[seeds]
+                ),
+                &crate::ID
+            )?;
+        }
+
+        let mut data = model_account.try_borrow_mut_data()?;
+        model_data.try_serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+// Checked here rather than failing inside the bubblegum CPI
+pub(crate) fn validate_model_metadata(name: &str, symbol: &str, uri: &str) -> Result<()> {
+    require!(name.len() <= MAX_NAME_LENGTH, Errors::MetadataTooLong);
+    require!(symbol.len() <= MAX_SYMBOL_LENGTH, Errors::MetadataTooLong);
+    require!(uri.len() <= MAX_URI_LENGTH, Errors::MetadataTooLong);
     Ok(())
-}
\ No newline at end of file
+}
+
+// Metadata of a model cNFT, verified as part of the task collection
+pub(crate) fn model_metadata(name: String, symbol: String, uri: String, seller_fee_basis_points: u16, collection_mint: Pubkey) -> MetadataArgs {
+    MetadataArgs {
+        name,
+        symbol,
+        uri,
+        creators: vec![],
+        seller_fee_basis_points,
+        primary_sale_happened: false,
+        is_mutable: false,
+        edition_nonce: Some(0),
+        uses: None,
+        collection: Some(Collection {
+            verified: true,
+            key: collection_mint,
+        }),
+        token_program_version: TokenProgramVersion::Original,
+        token_standard: Some(TokenStandard::NonFungible),
+    }
+}
@@ -1,16 +1,44 @@
 pub mod setup;
 mod mint;
 mod mint_to_task;
-mod close_account;
+mod mint_batch_to_task;
 mod get_model;
 mod post_request;
 mod submit_pred;
+mod commit_pred;
+mod reveal_pred;
 mod aggregate;
+mod expire_request;
+mod set_model_operator;
+mod revoke_model_operator;
+mod propose_admin;
+mod accept_admin;
+mod update_config;
+mod set_paused;
+mod set_task_paused;
+mod update_task;
+mod close_request;
+mod close_task;
+mod retire_model;
 
 pub use mint::*;
-pub use close_account::*;
 pub use mint_to_task::*;
+pub use mint_batch_to_task::*;
 pub use get_model::*;
 pub use post_request::*;
 pub use submit_pred::*;
-pub use aggregate::*;
\ No newline at end of file
+pub use commit_pred::*;
+pub use reveal_pred::*;
+pub use aggregate::*;
+pub use expire_request::*;
+pub use set_model_operator::*;
+pub use revoke_model_operator::*;
+pub use propose_admin::*;
+pub use accept_admin::*;
+pub use update_config::*;
+pub use set_paused::*;
+pub use set_task_paused::*;
+pub use update_task::*;
+pub use close_request::*;
+pub use close_task::*;
+pub use retire_model::*;
\ No newline at end of file
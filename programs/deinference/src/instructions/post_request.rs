@@ -1,17 +1,24 @@
 use anchor_lang::prelude::*;
-use crate::{error::Errors, state::{InferenceRequest, ProgramState, RequestStatus, TaskData}};
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::associated_token::{create_idempotent, get_associated_token_address, AssociatedToken, Create};
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use crate::error::Errors;
+use crate::state::{FeeEscrow, InferenceRequest, ProgramState, ProtocolConfig, RequestStatus, TaskData};
 
 #[event]
 pub struct Request {
-    request_id: u16,
+    request_id: u64,
     task_collection: Pubkey,
     request_data: Vec<u8>,
     posted_at: i64,
+    deadline: i64,
+    commit_deadline: Option<i64>,
+    fee: u64,
     status: RequestStatus
 }
 
 #[derive(Accounts)]
-#[instruction(request_id: u16)]
+#[instruction(request_data: Vec<u8>)]
 pub struct PostRequest<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -19,7 +26,8 @@ pub struct PostRequest<'info> {
     #[account(
         mut,
         seeds = [b"collection123", collection_mint.key().as_ref()],
-        bump
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
     )]
     pub task_data: Account<'info, TaskData>,
 
@@ -30,22 +38,49 @@ pub struct PostRequest<'info> {
     )]
     pub program_state: Account<'info, ProgramState>,
 
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
     #[account(
         init,
         payer = user,
-        // 8 (disc) + 32 (user) + 32 (task_collection) + 2 (request_id) + (4 (vec length prefix) + 32 (max length prefix) * 1 (u8)  (input_data)) + 8 (posted_at) + 1 (status) + 4 (Vec length prefix) + 10 (max_num_results) * (32 * 2) (results_entry)  = 763
-        space = 763,
-        seeds = [b"request", request_id.to_le_bytes().as_ref()],
+        // Sized for the input and the aggregate, submit_pred grows it for each result
+        space = InferenceRequest::space(request_data.len(), 0, task_data.schema.output.prediction_size()),
+        // Requests are numbered by a program wide counter, so ids never collide
+        seeds = [b"request", program_state.request_count.to_le_bytes().as_ref()],
         bump
     )]
     pub request_state: Account<'info, InferenceRequest>,
 
+    #[account(
+        init,
+        payer = user,
+        space = FeeEscrow::SPACE,
+        seeds = [b"escrow", request_state.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, FeeEscrow>,
+
     /// CHECK: unsafe
     pub collection_mint: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
+
+    // Token accounts, only required when the task charges fees in an SPL mint
+    pub fee_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_token_account: Option<Account<'info, TokenAccount>>,
+    /// CHECK: escrow's associated token account, checked against the derived address and created here
+    #[account(mut)]
+    pub escrow_token_account: Option<UncheckedAccount<'info>>,
+    pub token_program: Option<Program<'info, Token>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
 }
 
-pub fn post_request(ctx: Context<PostRequest>, request_id: u16, request_data: Vec<u8>, required_predictions: u16) -> Result<()> {
+pub fn post_request(ctx: Context<PostRequest>, request_data: Vec<u8>, required_predictions: u16, fee: u64, deadline: Option<i64>) -> Result<()> {
     let task_data = &ctx.accounts.task_data;
 
     // Verify the collection mint matches the task data account
@@ -54,26 +89,127 @@ pub fn post_request(ctx: Context<PostRequest>, request_id: u16, request_data: Ve
         ctx.accounts.collection_mint.key()
     );
 
+    let config = &ctx.accounts.config;
+    require!(task_data.model_count >= config.min_model_count, Errors::ModelCountTooLow);
+    require!(request_data.len() <= config.max_input_size.into(), Errors::InputTooLarge);
+    require!(required_predictions <= config.max_results, Errors::TooManyResults);
+    require!(required_predictions >= task_data.settings.min_predictions, Errors::TooFewPredictions);
+    require!(fee >= task_data.settings.min_fee, Errors::FeeTooLow);
+    task_data.schema.validate_input(&request_data)?;
+
+    // Take the next request id
+    let program_state = &mut ctx.accounts.program_state;
+    let request_id = program_state.request_count;
+    program_state.request_count = program_state.request_count.checked_add(1)
+        .ok_or(Errors::RequestCountOverflow)?;
+
     let clock = Clock::get()?;
     let posted_at = clock.unix_timestamp;
 
+    // Requests without an explicit deadline use the task's default time to live
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => posted_at.checked_add(task_data.settings.request_ttl).ok_or(error!(Errors::InvalidDeadline))?,
+    };
+    require!(deadline > posted_at, Errors::InvalidDeadline);
+
+    // Commit-reveal tasks take commits for their commit window, leaving the rest of the request for reveals
+    let commit_deadline = match task_data.settings.commit_window {
+        Some(commit_window) => {
+            let commit_deadline = posted_at.checked_add(commit_window).ok_or(error!(Errors::InvalidDeadline))?;
+            require!(commit_deadline < deadline, Errors::InvalidDeadline);
+            Some(commit_deadline)
+        }
+        None => None,
+    };
+
     emit!(Request{
-        request_id: request_id,
+        request_id,
         task_collection: *ctx.accounts.collection_mint.key,
         request_data: request_data.clone(),
-        posted_at: posted_at,
+        posted_at,
+        deadline,
+        commit_deadline,
+        fee,
         status: RequestStatus::Pending
     });
 
+    match task_data.fee_mint {
+        // Deposit the fee into escrow on top of its rent
+        None => transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                }
+            ),
+            fee
+        )?,
+        // Deposit the fee into an associated token account owned by the escrow
+        Some(fee_mint) => {
+            let accounts = &ctx.accounts;
+            let (Some(mint), Some(user_token_account), Some(escrow_token_account), Some(token_program), Some(associated_token_program)) = (
+                &accounts.fee_mint,
+                &accounts.user_token_account,
+                &accounts.escrow_token_account,
+                &accounts.token_program,
+                &accounts.associated_token_program,
+            ) else {
+                return err!(Errors::InvalidFeeTokenAccounts);
+            };
+            require_keys_eq!(mint.key(), fee_mint, Errors::InvalidFeeTokenAccounts);
+            require_keys_eq!(
+                escrow_token_account.key(),
+                get_associated_token_address(&accounts.escrow.key(), &fee_mint),
+                Errors::InvalidFeeTokenAccounts
+            );
+
+            // The escrow address follows from the request count, so anyone may have created its token account already
+            create_idempotent(CpiContext::new(
+                associated_token_program.to_account_info(),
+                Create {
+                    payer: accounts.user.to_account_info(),
+                    associated_token: escrow_token_account.to_account_info(),
+                    authority: accounts.escrow.to_account_info(),
+                    mint: mint.to_account_info(),
+                    system_program: accounts.system_program.to_account_info(),
+                    token_program: token_program.to_account_info(),
+                }
+            ))?;
+            token::transfer(
+                CpiContext::new(
+                    token_program.to_account_info(),
+                    token::Transfer {
+                        from: user_token_account.to_account_info(),
+                        to: escrow_token_account.to_account_info(),
+                        authority: accounts.user.to_account_info(),
+                    }
+                ),
+                fee
+            )?;
+        }
+    }
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.request = ctx.accounts.request_state.key();
+    escrow.user = *ctx.accounts.user.key;
+    escrow.mint = task_data.fee_mint;
+    escrow.amount = fee;
+    escrow.protocol_fee = ctx.accounts.config.protocol_fee(fee);
+
     // Store state info
     let request_state = &mut ctx.accounts.request_state;
-    request_state.input_data = request_data.try_into().expect("Vec length matched array length"); 
+    request_state.input_data = request_data;
     request_state.posted_at = posted_at;
+    request_state.deadline = deadline;
+    request_state.commit_deadline = commit_deadline;
     request_state.status = RequestStatus::Pending;
     request_state.task_collection = *ctx.accounts.collection_mint.key;
     request_state.request_id = request_id;
     request_state.user = *ctx.accounts.user.key;
     request_state.required_predictions = required_predictions;
+    request_state.result = None;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::ProgramState;
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"knowledge"],
+        bump,
+        has_one = admin @ Errors::NotAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+}
+
+pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+    // The current admin stays in charge until the proposed admin accepts, a new proposal replaces the last one
+    let program_state = &mut ctx.accounts.program_state;
+    program_state.pending_admin = Some(new_admin);
+
+    emit!(AdminProposed {
+        admin: program_state.admin,
+        pending_admin: new_admin,
+    });
+
+    Ok(())
+}
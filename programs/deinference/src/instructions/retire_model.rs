@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::{ModelData, TaskData};
+
+#[event]
+pub struct ModelRetired {
+    pub task_collection: Pubkey,
+    pub weights_hash: [u8; 32],
+    pub reputation: u8,
+}
+
+#[derive(Accounts)]
+#[instruction(weights_hash: [u8; 32])]
+pub struct RetireModel<'info> {
+    #[account(mut)]
+    pub model_owner: Signer<'info>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump
+    )]
+    pub task_data: Account<'info, TaskData>,
+
+    // Predictions the model already submitted are aggregated without it, see aggregate
+    #[account(
+        mut,
+        close = model_owner,
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump,
+        constraint = model_data.owner == model_owner.key() @ Errors::NotModelOwner
+    )]
+    pub model_data: Account<'info, ModelData>,
+}
+
+pub fn retire_model(ctx: Context<RetireModel>, weights_hash: [u8; 32]) -> Result<()> {
+    let task_data = &mut ctx.accounts.task_data;
+    task_data.model_count = task_data.model_count.checked_sub(1)
+        .ok_or(Errors::ModelCountOverflow)?;
+
+    emit!(ModelRetired {
+        task_collection: task_data.collection_mint,
+        weights_hash,
+        reputation: ctx.accounts.model_data.reputation,
+    });
+
+    Ok(())
+}
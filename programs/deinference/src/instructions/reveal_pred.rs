@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+use crate::state::{InferenceRequest, ProtocolConfig, RequestStatus, ResultEntry, TaskData};
+use crate::error::Errors;
+
+#[event]
+pub struct PredictionRevealed {
+    pub request_id: u64,
+    pub weights_hash: [u8; 32],
+    pub prediction: Vec<u8>,
+}
+
+#[derive(Accounts)]
+#[instruction(request_id: u64)]
+pub struct RevealPred<'info> {
+    // Knowing the prediction and salt is what authorizes a reveal, so anyone can send it
+    pub revealer: Signer<'info>,
+
+    // Already sized for the prediction when it was committed
+    #[account(
+        mut,
+        seeds = [b"request", request_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub request_state: Account<'info, InferenceRequest>,
+
+    #[account(
+        seeds = [b"collection123", request_state.task_collection.as_ref()],
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
+    )]
+    pub task_data: Account<'info, TaskData>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+pub fn reveal_pred(ctx: Context<RevealPred>, request_id: u64, weights_hash: [u8; 32], prediction: Vec<u8>, salt: [u8; 32]) -> Result<()> {
+    let task_data = &ctx.accounts.task_data;
+    let request_state = &mut ctx.accounts.request_state;
+
+    require_eq!(request_state.request_id, request_id);
+    require_eq!(request_state.status.clone(), RequestStatus::Pending, Errors::InvalidStatus);
+    let now = Clock::get()?.unix_timestamp;
+    require!(request_state.commit_deadline.is_some(), Errors::CommitRevealDisabled);
+    require!(!request_state.is_commit_phase(now), Errors::RevealWindowNotOpen);
+    require!(!request_state.is_expired(now), Errors::RequestExpired);
+    task_data.schema.output.validate_prediction(&prediction)?;
+
+    let entry = request_state.results
+        .iter_mut()
+        .find(|entry| entry.weights_hash == weights_hash && !entry.is_revealed())
+        .ok_or(error!(Errors::CommitNotFound))?;
+    require!(
+        entry.commitment == Some(ResultEntry::commitment_hash(&prediction, &salt, &weights_hash)),
+        Errors::InvalidReveal
+    );
+    entry.prediction = prediction.clone();
+    entry.commitment = None;
+
+    emit!(PredictionRevealed {
+        request_id,
+        weights_hash,
+        prediction,
+    });
+
+    Ok(())
+}
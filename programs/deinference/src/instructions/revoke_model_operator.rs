@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::ModelData;
+
+#[event]
+pub struct OperatorRevoked {
+    pub task_collection: Pubkey,
+    pub weights_hash: [u8; 32],
+    pub operator: Pubkey,
+}
+
+#[derive(Accounts)]
+#[instruction(weights_hash: [u8; 32], operator: Pubkey)]
+pub struct RevokeModelOperator<'info> {
+    #[account(mut)]
+    pub model_owner: Signer<'info>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    // Shrunk by one operator, refunding the freed rent to the owner
+    #[account(
+        mut,
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump,
+        realloc = ModelData::space(model_data.operators.len().saturating_sub(1)),
+        realloc::payer = model_owner,
+        realloc::zero = false
+    )]
+    pub model_data: Account<'info, ModelData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn revoke_model_operator(ctx: Context<RevokeModelOperator>, weights_hash: [u8; 32], operator: Pubkey) -> Result<()> {
+    let model = &mut ctx.accounts.model_data;
+    require_keys_eq!(model.owner, ctx.accounts.model_owner.key(), Errors::NotModelOwner);
+
+    // Predictions the operator already submitted stay valid and are still paid out
+    model.remove_operator(&operator)?;
+
+    emit!(OperatorRevoked {
+        task_collection: model.task_collection,
+        weights_hash,
+        operator,
+    });
+
+    Ok(())
+}
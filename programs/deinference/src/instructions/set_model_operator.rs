@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::payout::BPS_DENOMINATOR;
+use crate::state::ModelData;
+
+#[event]
+pub struct OperatorSet {
+    pub task_collection: Pubkey,
+    pub weights_hash: [u8; 32],
+    pub operator: Pubkey,
+    pub operator_share_bps: u16,
+}
+
+#[derive(Accounts)]
+#[instruction(weights_hash: [u8; 32], operator: Pubkey)]
+pub struct SetModelOperator<'info> {
+    #[account(mut)]
+    pub model_owner: Signer<'info>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    // Grown by one operator unless it is already registered, the owner funds the extra rent
+    #[account(
+        mut,
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump,
+        realloc = ModelData::space(model_data.operators.len() + usize::from(!model_data.operators.contains(&operator))),
+        realloc::payer = model_owner,
+        realloc::zero = false
+    )]
+    pub model_data: Account<'info, ModelData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn set_model_operator(ctx: Context<SetModelOperator>, weights_hash: [u8; 32], operator: Pubkey, operator_share_bps: u16) -> Result<()> {
+    require!(operator_share_bps <= BPS_DENOMINATOR, Errors::InvalidOperatorShare);
+
+    let model = &mut ctx.accounts.model_data;
+    require_keys_eq!(model.owner, ctx.accounts.model_owner.key(), Errors::NotModelOwner);
+
+    // The share applies to whichever operator submits for the model
+    model.add_operator(operator)?;
+    model.operator_share_bps = operator_share_bps;
+
+    emit!(OperatorSet {
+        task_collection: model.task_collection,
+        weights_hash,
+        operator,
+        operator_share_bps,
+    });
+
+    Ok(())
+}
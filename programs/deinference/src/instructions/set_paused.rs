@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::{ProgramState, ProtocolConfig};
+
+#[event]
+pub struct PauseSet {
+    pub task_collection: Option<Pubkey>, // None for the global switch
+    pub paused: bool,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"knowledge"],
+        bump,
+        has_one = admin @ Errors::NotAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+    // Expiry, refunds and closing accounts stay available while paused so users can exit
+    ctx.accounts.config.paused = paused;
+
+    emit!(PauseSet {
+        task_collection: None,
+        paused,
+    });
+
+    Ok(())
+}
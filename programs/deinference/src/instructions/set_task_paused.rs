@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::{ProgramState, TaskData};
+use crate::instructions::PauseSet;
+
+#[derive(Accounts)]
+pub struct SetTaskPaused<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"knowledge"],
+        bump,
+        has_one = admin @ Errors::NotAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump
+    )]
+    pub task_data: Account<'info, TaskData>,
+}
+
+pub fn set_task_paused(ctx: Context<SetTaskPaused>, paused: bool) -> Result<()> {
+    let task_data = &mut ctx.accounts.task_data;
+    task_data.paused = paused;
+
+    emit!(PauseSet {
+        task_collection: Some(task_data.collection_mint),
+        paused,
+    });
+
+    Ok(())
+}
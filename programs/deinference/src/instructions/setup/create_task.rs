@@ -1,38 +1,61 @@
 use anchor_lang::prelude::*;
-use crate::state::TaskData;
+use anchor_spl::token::Mint;
+use mpl_token_metadata::accounts::Metadata as MetadataAccount;
+use crate::error::Errors;
+use crate::schema::TaskSchema;
+use crate::state::{TaskData, TaskSettings};
 use crate::Metadata;
 
 #[derive(Accounts)]
 pub struct CreateTask<'info> {
-    // space = account disc (8) + mint (32) + model_count (2) + vec_size (4)
-    // + max_models (4 for now) * model_data(length_prefix (4) + string_length (32) + pubkey (32) + leaf_index (2) + reputation (1))
     #[account(
-        init, payer = payer, space = 330, seeds = [b"collection123", collection_mint.key().as_ref()], bump
+        init, payer = payer, space = TaskData::SPACE, seeds = [b"collection123", collection_mint.key().as_ref()], bump
     )]
     pub task_data: Account<'info, TaskData>,
 
     /// CHECK: unsafe
     pub collection_mint: UncheckedAccount<'info>,
 
+    /// CHECK: checked to be the collection's metadata account and deserialized in the instruction
+    #[account(
+        seeds = [b"metadata", mpl_token_metadata.key().as_ref(), collection_mint.key().as_ref()],
+        seeds::program = mpl_token_metadata.key(),
+        bump,
+        owner = mpl_token_metadata.key() @ Errors::InvalidCollectionMetadata
+    )]
+    pub collection_metadata: UncheckedAccount<'info>,
+
+    // SPL mint request fees are paid in, omit to charge fees in lamports
+    pub fee_mint: Option<Account<'info, Mint>>,
+
+    // Must be the collection's update authority, becomes the task owner
     #[account(mut)]
     pub payer: Signer<'info>,
     pub mpl_token_metadata: Program<'info, Metadata>, 
     pub system_program: Program<'info, System>,
 }
 
-pub fn create_task(ctx: Context<CreateTask>) -> Result<()> {
+pub fn create_task(ctx: Context<CreateTask>, schema: TaskSchema, settings: TaskSettings) -> Result<()> {
+    schema.validate()?;
+    settings.validate(&schema.output.kind)?;
     let collection_mint_pk = ctx.accounts.collection_mint.key;
+
+    // Only whoever controls the collection can register a task for it
+    let metadata = MetadataAccount::try_from(&ctx.accounts.collection_metadata.to_account_info())
+        .map_err(|_| error!(Errors::InvalidCollectionMetadata))?;
+    require_keys_eq!(metadata.mint, *collection_mint_pk, Errors::InvalidCollectionMetadata);
+    require_keys_eq!(metadata.update_authority, ctx.accounts.payer.key(), Errors::NotCollectionAuthority);
     
     let task_data = &mut ctx.accounts.task_data;
     task_data.collection_mint = *collection_mint_pk;
+    task_data.owner = ctx.accounts.payer.key();
+    task_data.schema = schema;
+    task_data.settings = settings;
+    task_data.fee_mint = ctx.accounts.fee_mint.as_ref().map(|mint| mint.key());
+    task_data.paused = false;
     task_data.model_count = 0;
 
     msg!("DONE CREATING TASK");
 
     Ok(())
 }
-
-
-
-
-
@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 use mpl_bubblegum::instructions::CreateTreeConfigCpiBuilder;
-use crate::{Noop, MplBubblegum, SplAccountCompression, state::ProgramState, state::TreeInfo};
+use crate::{Noop, MplBubblegum, SplAccountCompression, error::Errors, state::ProgramState, state::TreeInfo, state::MAX_TREES};
 
 #[derive(Accounts)]
 pub struct CreateTree<'info> {
@@ -16,6 +16,8 @@ pub struct CreateTree<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    pub admin: Signer<'info>,
+
     #[account(
         seeds = [b"tree_owner", tree.key().as_ref()],
         bump
@@ -23,10 +25,16 @@ pub struct CreateTree<'info> {
     /// CHECK: This account used as a signing PDA only
     pub tree_owner: UncheckedAccount<'info>,
 
+    // Grown by one tree, the payer funds the extra rent
     #[account(
         mut,
         seeds = [b"knowledge"],
-        bump
+        bump,
+        has_one = admin @ Errors::NotAdmin,
+        constraint = program_state.trees.len() < MAX_TREES @ Errors::TooManyTrees,
+        realloc = ProgramState::space(program_state.trees.len() + 1),
+        realloc::payer = payer,
+        realloc::zero = false
     )]
     pub program_state: Account<'info, ProgramState>,
 
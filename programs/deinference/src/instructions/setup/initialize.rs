@@ -1,29 +1,44 @@
 use anchor_lang::prelude::*;
-use std::str::FromStr;
-use crate::state::ProgramState;
+use crate::error::Errors;
+use crate::program::KnowledgeManager;
+use crate::state::{ProgramState, ProtocolConfig};
 
 #[derive(Accounts)]
 pub struct Initialize<'info> {
-    // space = account disc (8) + pubkey (32) + vec size (4) + tree_count (2) + max_trees (1) * tree info (64)
+    // Starts without any trees, create_tree grows the account for each one
     #[account(
-        init, payer = payer, space = 46 + 1 * 66, seeds = [b"knowledge"], bump
+        init, payer = payer, space = ProgramState::space(0), seeds = [b"knowledge"], bump
     )]
     pub program_state: Account<'info, ProgramState>,
 
+    #[account(
+        init, payer = payer, space = ProtocolConfig::SPACE, seeds = [b"config"], bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    // Only the upgrade authority can initialize, so nobody can front-run a deployment and take it over
     #[account(mut)]
     pub payer: Signer<'info>,
 
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, KnowledgeManager>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ Errors::NotUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+
     pub system_program: Program<'info, System>,
 }
 
-pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+pub fn initialize(ctx: Context<Initialize>, admin: Pubkey) -> Result<()> {
     // Initialize program state account
     let program_state = &mut ctx.accounts.program_state;
-    let creator_pbk_str = "2gUrmvYsLTpXB5VwjP2ZpXD4kY4HWRP89aDzQQ7TKbwh";
-    let creator_pbk = Pubkey::from_str(creator_pbk_str)
-        .map_err(|_| ProgramError::InvalidArgument)?;
-    program_state.creator = creator_pbk;
+    program_state.admin = admin;
+    program_state.pending_admin = None;
     program_state.tree_count = 0;
+    program_state.request_count = 0;
+
+    // Protocol fees go to the admin until a treasury is configured
+    ctx.accounts.config.set_inner(ProtocolConfig::with_defaults(admin));
 
     Ok(())
-}
\ No newline at end of file
+}
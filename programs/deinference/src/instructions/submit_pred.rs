@@ -1,17 +1,27 @@
 use anchor_lang::prelude::*;
-use crate::state::{InferenceRequest, RequestStatus, ResultEntry, TaskData};
+use crate::state::{InferenceRequest, ModelData, ModelIdentity, ProtocolConfig, RequestStatus, ResultEntry, TaskData};
 use crate::error::Errors;
+use crate::verify::{eth_signed_message, prediction_message, verify_secp256k1_instruction};
 
 #[derive(Accounts)]
-#[instruction(request_id: u16)]
+#[instruction(request_id: u64, weights_hash: [u8; 32])]
 pub struct SubmitPred<'info> {
     #[account(mut)]
     pub model_owner: Signer<'info>,
 
+    // Grown by one result, the submitter funds the extra rent
     #[account(
         mut,
         seeds = [b"request", request_id.to_le_bytes().as_ref()],
-        bump
+        bump,
+        constraint = request_state.results.len() < config.max_results.into() @ Errors::TooManyResults,
+        realloc = InferenceRequest::space(
+            request_state.input_data.len(),
+            request_state.results.len() + 1,
+            task_data.schema.output.prediction_size()
+        ),
+        realloc::payer = model_owner,
+        realloc::zero = false
     )]
     pub request_state: Account<'info, InferenceRequest>,
 
@@ -20,32 +30,84 @@ pub struct SubmitPred<'info> {
 
     #[account(
         seeds = [b"collection123", collection_mint.key().as_ref()],
-        bump
+        bump,
+        constraint = !task_data.paused @ Errors::TaskPaused
     )]
     pub task_data: Account<'info, TaskData>,
 
+    #[account(
+        seeds = [b"model", collection_mint.key().as_ref(), weights_hash.as_ref()],
+        bump
+    )]
+    pub model_data: Account<'info, ModelData>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.paused @ Errors::ProgramPaused
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Sysvar account for instruction introspection, read for predictions attested by an Ethereum identity
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instruction_sysvar: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
-pub fn submit_pred(ctx: Context<SubmitPred>, request_id: u16, weights_hash: [u8; 32], prediction: Vec<u8>) -> Result<()> {
+pub fn submit_pred(ctx: Context<SubmitPred>, request_id: u64, weights_hash: [u8; 32], prediction: Vec<u8>) -> Result<()> {
     let task_data = &ctx.accounts.task_data;
     let request_state = &mut ctx.accounts.request_state;
     
     require_keys_eq!(task_data.collection_mint, *ctx.accounts.collection_mint.key);
-    require!(task_data.has_model(&weights_hash), Errors::ModelNotFound);
+    // The model account is per task, so the request must belong to the same task
+    require_keys_eq!(request_state.task_collection, *ctx.accounts.collection_mint.key);
+    let model_data = &ctx.accounts.model_data;
+    require!(model_data.reputation >= task_data.settings.min_reputation, Errors::ReputationTooLow);
     require_eq!(request_state.request_id, request_id);
     require_eq!(request_state.status.clone(), RequestStatus::Pending, Errors::InvalidStatus);
+    require!(!request_state.is_expired(Clock::get()?.unix_timestamp), Errors::RequestExpired);
+    require!(request_state.commit_deadline.is_none(), Errors::CommitRevealRequired);
     require!(
         !request_state.results.iter().any(|entry| entry.weights_hash == weights_hash),
         Errors::DuplicatePrediction
     );
-    
+    task_data.schema.output.validate_prediction(&prediction)?;
+
+    let submitter = authorize_submitter(
+        model_data,
+        ctx.accounts.model_owner.key,
+        &ctx.accounts.instruction_sysvar,
+        &prediction_message(&crate::ID, request_id, &weights_hash, &prediction)
+    )?;
 
     let result = ResultEntry {
-        weights_hash: weights_hash,
-        prediction: prediction
+        weights_hash,
+        submitter,
+        prediction,
+        commitment: None
     };
 
     request_state.results.push(result);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// The owner and its operators submit directly. A model attested by an Ethereum identity also accepts submissions
+// that identity signed over `message`, relayed by anyone, and they are recorded as the owner's own
+pub(crate) fn authorize_submitter(
+    model_data: &ModelData,
+    signer: &Pubkey,
+    instruction_sysvar: &AccountInfo,
+    message: &[u8]
+) -> Result<Pubkey> {
+    match model_data.identity {
+        _ if model_data.can_submit(signer) => Ok(*signer),
+        ModelIdentity::Secp256k1 { eth_address } => {
+            verify_secp256k1_instruction(instruction_sysvar, &eth_address, &eth_signed_message(message))
+                .map_err(|_| error!(Errors::UnauthorizedSubmitter))?;
+            Ok(model_data.owner)
+        }
+        ModelIdentity::Ed25519 => Err(Errors::UnauthorizedSubmitter.into()),
+    }
+}
@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::{ProgramState, ProtocolConfig};
+
+#[event]
+pub struct ConfigUpdated {
+    pub initial_reputation: u8,
+    pub max_results: u16,
+    pub max_input_size: u16,
+    pub min_model_count: u16,
+    pub protocol_fee_bps: u16,
+    pub treasury: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"knowledge"],
+        bump,
+        has_one = admin @ Errors::NotAdmin
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+}
+
+pub fn update_config(ctx: Context<UpdateConfig>, mut new_config: ProtocolConfig) -> Result<()> {
+    // Requests already posted keep the protocol fee they were posted with
+    new_config.validate()?;
+    // Pausing is only changed through set_paused
+    new_config.paused = ctx.accounts.config.paused;
+
+    emit!(ConfigUpdated {
+        initial_reputation: new_config.initial_reputation,
+        max_results: new_config.max_results,
+        max_input_size: new_config.max_input_size,
+        min_model_count: new_config.min_model_count,
+        protocol_fee_bps: new_config.protocol_fee_bps,
+        treasury: new_config.treasury,
+    });
+
+    ctx.accounts.config.set_inner(new_config);
+
+    Ok(())
+}
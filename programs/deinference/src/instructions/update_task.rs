@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::state::{TaskData, TaskSettings};
+
+#[event]
+pub struct TaskUpdated {
+    pub task_collection: Pubkey,
+    pub min_predictions: u16,
+    pub min_reputation: u8,
+    pub min_fee: u64,
+    pub request_ttl: i64,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTask<'info> {
+    pub owner: Signer<'info>,
+
+    /// CHECK: unsafe
+    pub collection_mint: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collection123", collection_mint.key().as_ref()],
+        bump,
+        has_one = owner @ Errors::NotTaskOwner
+    )]
+    pub task_data: Account<'info, TaskData>,
+}
+
+// Pending requests keep the deadline, fee and prediction count they were posted with,
+// the algorithm, reputation policy and minimum reputation apply from now on
+pub fn update_task(ctx: Context<UpdateTask>, settings: TaskSettings) -> Result<()> {
+    let task_data = &mut ctx.accounts.task_data;
+    settings.validate(&task_data.schema.output.kind)?;
+
+    emit!(TaskUpdated {
+        task_collection: task_data.collection_mint,
+        min_predictions: settings.min_predictions,
+        min_reputation: settings.min_reputation,
+        min_fee: settings.min_fee,
+        request_ttl: settings.request_ttl,
+    });
+
+    task_data.settings = settings;
+
+    Ok(())
+}
@@ -3,7 +3,13 @@ use mpl_bubblegum::programs::{MPL_BUBBLEGUM_ID, SPL_ACCOUNT_COMPRESSION_ID, SPL_
 use mpl_token_metadata::programs::MPL_TOKEN_METADATA_ID;
 use crate::instructions::*;
 use crate::instructions::setup::*;
+use crate::schema::TaskSchema;
+use crate::state::{ProtocolConfig, TaskSettings};
 mod verify;
+mod payout;
+mod prediction;
+mod reputation;
+mod schema;
 mod instructions;
 mod state;
 mod error;
@@ -50,8 +56,33 @@ impl anchor_lang::Id for Metadata {
 pub mod knowledge_manager {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        instructions::setup::initialize(ctx)?;
+    pub fn initialize(ctx: Context<Initialize>, admin: Pubkey) -> Result<()> {
+        instructions::setup::initialize(ctx, admin)?;
+        Ok(())
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::propose_admin(ctx, new_admin)?;
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        instructions::accept_admin(ctx)?;
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, new_config: ProtocolConfig) -> Result<()> {
+        instructions::update_config(ctx, new_config)?;
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        instructions::set_paused(ctx, paused)?;
+        Ok(())
+    }
+
+    pub fn set_task_paused(ctx: Context<SetTaskPaused>, paused: bool) -> Result<()> {
+        instructions::set_task_paused(ctx, paused)?;
         Ok(())
     }
 
@@ -71,8 +102,13 @@ pub mod knowledge_manager {
         Ok(())
     }
 
-    pub fn create_task(ctx: Context<CreateTask>) -> Result<()> {
-        instructions::setup::create_task(ctx)?;
+    pub fn create_task(ctx: Context<CreateTask>, schema: TaskSchema, settings: TaskSettings) -> Result<()> {
+        instructions::setup::create_task(ctx, schema, settings)?;
+        Ok(())
+    }
+
+    pub fn update_task(ctx: Context<UpdateTask>, settings: TaskSettings) -> Result<()> {
+        instructions::update_task(ctx, settings)?;
         Ok(())
     }
 
@@ -81,35 +117,93 @@ pub mod knowledge_manager {
         name: String,
         symbol: String,
         uri: String,
+        weights_hash: [u8; 32],
         weights_signature: [u8; 64],
         seller_fee_basis_points: u16
     ) -> Result<()> {
-        instructions::mint_to_task(ctx, name, symbol, uri, weights_signature, seller_fee_basis_points)?;
+        instructions::mint_to_task(ctx, name, symbol, uri, weights_hash, weights_signature, seller_fee_basis_points)?;
         Ok(())
     }
 
-    pub fn post_request(ctx:Context<PostRequest>, request_id: u16, data: Vec<u8>, required_predictions: u16) -> Result<()> {
-        instructions::post_request(ctx, request_id, data, required_predictions)?;
+    pub fn mint_to_task_eth(
+        ctx: Context<MintToTask>,
+        name: String,
+        symbol: String,
+        uri: String,
+        weights_hash: [u8; 32],
+        eth_address: [u8; 20],
+        seller_fee_basis_points: u16
+    ) -> Result<()> {
+        instructions::mint_to_task_eth(ctx, name, symbol, uri, weights_hash, eth_address, seller_fee_basis_points)?;
         Ok(())
     }
 
-    pub fn submit_pred(ctx:Context<SubmitPred>, request_id: u16, weights_hash: [u8; 32], prediction: Vec<u8>) -> Result<()> {
+    pub fn mint_batch_to_task<'info>(
+        ctx: Context<'_, '_, '_, 'info, MintBatchToTask<'info>>,
+        models: Vec<ModelMint>,
+        seller_fee_basis_points: u16
+    ) -> Result<()> {
+        instructions::mint_batch_to_task(ctx, models, seller_fee_basis_points)?;
+        Ok(())
+    }
+
+    pub fn post_request(ctx:Context<PostRequest>, data: Vec<u8>, required_predictions: u16, fee: u64, deadline: Option<i64>) -> Result<()> {
+        instructions::post_request(ctx, data, required_predictions, fee, deadline)?;
+        Ok(())
+    }
+
+    pub fn submit_pred(ctx:Context<SubmitPred>, request_id: u64, weights_hash: [u8; 32], prediction: Vec<u8>) -> Result<()> {
         instructions::submit_pred(ctx, request_id, weights_hash, prediction)?;
         Ok(())
     }
 
-    pub fn aggregate(ctx:Context<Aggregate>, request_id: u16, algorithm: AggregationAlgorithm) -> Result<()> {
+    pub fn commit_pred(ctx: Context<CommitPred>, request_id: u64, weights_hash: [u8; 32], commitment: [u8; 32]) -> Result<()> {
+        instructions::commit_pred(ctx, request_id, weights_hash, commitment)?;
+        Ok(())
+    }
+
+    pub fn reveal_pred(ctx: Context<RevealPred>, request_id: u64, weights_hash: [u8; 32], prediction: Vec<u8>, salt: [u8; 32]) -> Result<()> {
+        instructions::reveal_pred(ctx, request_id, weights_hash, prediction, salt)?;
+        Ok(())
+    }
+
+    pub fn aggregate<'info>(ctx: Context<'_, '_, 'info, 'info, Aggregate<'info>>, request_id: u64, algorithm: Option<AggregationAlgorithm>) -> Result<()> {
         instructions::aggregate(ctx, request_id, algorithm)?;
         Ok(())
     }
 
+    pub fn expire_request(ctx: Context<ExpireRequest>, request_id: u64) -> Result<()> {
+        instructions::expire_request(ctx, request_id)?;
+        Ok(())
+    }
+
+    pub fn set_model_operator(ctx: Context<SetModelOperator>, weights_hash: [u8; 32], operator: Pubkey, operator_share_bps: u16) -> Result<()> {
+        instructions::set_model_operator(ctx, weights_hash, operator, operator_share_bps)?;
+        Ok(())
+    }
+
+    pub fn revoke_model_operator(ctx: Context<RevokeModelOperator>, weights_hash: [u8; 32], operator: Pubkey) -> Result<()> {
+        instructions::revoke_model_operator(ctx, weights_hash, operator)?;
+        Ok(())
+    }
+
     pub fn get_model(ctx: Context<GetModel>, weights_hash: [u8; 32]) -> Result<()> {
         instructions::get_model(ctx, weights_hash)?; 
         Ok(())
     }
 
-    pub fn close_account(ctx: Context<CloseAccount>) -> Result<()> {
-        instructions::close_account(ctx)?;
+    pub fn close_request(ctx: Context<CloseRequest>, request_id: u64) -> Result<()> {
+        instructions::close_request(ctx, request_id)?;
+        Ok(())
+    }
+
+    pub fn close_task(ctx: Context<CloseTask>) -> Result<()> {
+        instructions::close_task(ctx)?;
+        Ok(())
+    }
+
+    pub fn retire_model(ctx: Context<RetireModel>, weights_hash: [u8; 32]) -> Result<()> {
+        instructions::retire_model(ctx, weights_hash)?;
         Ok(())
     }
 }
@@ -0,0 +1,221 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::{create_idempotent, get_associated_token_address, AssociatedToken, Create};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount};
+use crate::error::Errors;
+use crate::reputation::Outcome;
+use crate::state::FeeEscrow;
+
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+// Accurate predictions earn twice the share of predictions in the neutral band, penalized ones earn nothing
+pub fn accuracy_multiplier(outcome: Outcome) -> u64 {
+    match outcome {
+        Outcome::Reward => 2,
+        Outcome::Neutral => 1,
+        Outcome::Penalty => 0,
+    }
+}
+
+// Payout weight of a single result, based on the reputation the model had when the request was aggregated
+pub fn payout_weight(reputation: u8, outcome: Outcome) -> u64 {
+    reputation as u64 * accuracy_multiplier(outcome)
+}
+
+// Splits `fee` proportionally to `weights`. Every share is rounded down to a whole lamport
+// (share_i = floor(fee * weight_i / total_weight)) and whatever is left, including the whole
+// fee when all weights are zero, is returned as the remainder to refund.
+pub fn split_fee(fee: u64, weights: &[u64]) -> (Vec<u64>, u64) {
+    let total_weight: u128 = weights.iter().map(|weight| *weight as u128).sum();
+    if total_weight == 0 {
+        return (vec![0; weights.len()], fee)
+    }
+
+    let shares: Vec<u64> = weights
+        .iter()
+        .map(|weight| (fee as u128 * *weight as u128 / total_weight) as u64)
+        .collect();
+    let remainder = fee - shares.iter().sum::<u64>();
+
+    (shares, remainder)
+}
+
+// Splits a share between the model owner and the operator that submitted for it. The operator's
+// part is rounded down, so the owner keeps any dust
+pub fn split_operator_share(amount: u64, operator_share_bps: u16) -> (u64, u64) {
+    let operator_amount = (amount as u128 * operator_share_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    (amount - operator_amount, operator_amount)
+}
+
+// Accounts needed to pay out a fee held in the escrow's associated token account
+pub struct TokenPayout<'info> {
+    payer: AccountInfo<'info>,
+    escrow: AccountInfo<'info>,
+    escrow_token_account: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    associated_token_program: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+}
+
+impl<'info> TokenPayout<'info> {
+    // Checks the token accounts passed for an escrow holding an SPL fee, None if the fee is in lamports.
+    // `payer` funds any associated token accounts created for recipients
+    pub fn for_escrow(
+        escrow: &Account<'info, FeeEscrow>,
+        escrow_token_account: Option<&Account<'info, TokenAccount>>,
+        mint: Option<&Account<'info, Mint>>,
+        token_program: Option<&Program<'info, Token>>,
+        associated_token_program: Option<&Program<'info, AssociatedToken>>,
+        payer: &Signer<'info>,
+        system_program: &Program<'info, System>,
+    ) -> Result<Option<Self>> {
+        let Some(fee_mint) = escrow.mint else {
+            return Ok(None);
+        };
+        let (Some(escrow_token_account), Some(mint), Some(token_program), Some(associated_token_program)) =
+            (escrow_token_account, mint, token_program, associated_token_program)
+        else {
+            return err!(Errors::InvalidFeeTokenAccounts);
+        };
+        require_keys_eq!(mint.key(), fee_mint, Errors::InvalidFeeTokenAccounts);
+        require_keys_eq!(
+            escrow_token_account.key(),
+            get_associated_token_address(&escrow.key(), &fee_mint),
+            Errors::InvalidFeeTokenAccounts
+        );
+
+        Ok(Some(TokenPayout {
+            payer: payer.to_account_info(),
+            escrow: escrow.to_account_info(),
+            escrow_token_account: escrow_token_account.to_account_info(),
+            mint: mint.to_account_info(),
+            token_program: token_program.to_account_info(),
+            associated_token_program: associated_token_program.to_account_info(),
+            system_program: system_program.to_account_info(),
+        }))
+    }
+
+    // Finds the owner's associated token account for the fee mint among the remaining accounts
+    pub fn find_token_account<'a>(&self, accounts: &'a [AccountInfo<'info>], owner: &AccountInfo<'info>) -> Result<&'a AccountInfo<'info>> {
+        let address = get_associated_token_address(owner.key, self.mint.key);
+        accounts
+            .iter()
+            .find(|account| account.key() == address)
+            .ok_or(error!(Errors::MissingPayoutAccount))
+    }
+
+    // Transfers `amount` from the escrow to the owner's associated token account, creating it first if needed
+    pub fn pay(&self, owner: &AccountInfo<'info>, owner_token_account: &AccountInfo<'info>, amount: u64, escrow_seeds: &[&[u8]]) -> Result<()> {
+        require_keys_eq!(
+            owner_token_account.key(),
+            get_associated_token_address(owner.key, self.mint.key),
+            Errors::InvalidFeeTokenAccounts
+        );
+        create_idempotent(CpiContext::new(
+            self.associated_token_program.clone(),
+            Create {
+                payer: self.payer.clone(),
+                associated_token: owner_token_account.clone(),
+                authority: owner.clone(),
+                mint: self.mint.clone(),
+                system_program: self.system_program.clone(),
+                token_program: self.token_program.clone(),
+            }
+        ))?;
+        token::transfer(
+            CpiContext::new_with_signer(
+                self.token_program.clone(),
+                token::Transfer {
+                    from: self.escrow_token_account.clone(),
+                    to: owner_token_account.clone(),
+                    authority: self.escrow.clone(),
+                },
+                &[escrow_seeds]
+            ),
+            amount
+        )
+    }
+
+    // Refunds whatever the escrow token account still holds to the owner, including tokens anyone sent to it
+    // on top of the fee, then closes it with its rent going to the owner as well. Returns the amount refunded
+    pub fn refund_and_close(
+        &self,
+        owner: &AccountInfo<'info>,
+        owner_token_account: Option<&AccountInfo<'info>>,
+        escrow_seeds: &[&[u8]]
+    ) -> Result<u64> {
+        let balance = token::accessor::amount(&self.escrow_token_account)?;
+        if balance > 0 {
+            let owner_token_account = owner_token_account.ok_or(error!(Errors::InvalidFeeTokenAccounts))?;
+            self.pay(owner, owner_token_account, balance, escrow_seeds)?;
+        }
+        token::close_account(CpiContext::new_with_signer(
+            self.token_program.clone(),
+            CloseAccount {
+                account: self.escrow_token_account.clone(),
+                destination: owner.clone(),
+                authority: self.escrow.clone(),
+            },
+            &[escrow_seeds]
+        ))?;
+        Ok(balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_fee_exact() {
+        assert_eq!(split_fee(1_000, &[1, 1, 2]), (vec![250, 250, 500], 0));
+        assert_eq!(split_fee(1_000, &[5]), (vec![1_000], 0));
+    }
+
+    #[test]
+    fn test_split_fee_rounds_down_and_refunds_dust() {
+        // 1000 / 3 = 333.33..., each share is rounded down and one lamport is refunded
+        assert_eq!(split_fee(1_000, &[1, 1, 1]), (vec![333, 333, 333], 1));
+        // 7 lamports over weights 2 and 3: 2.8 -> 2, 4.2 -> 4
+        assert_eq!(split_fee(7, &[2, 3]), (vec![2, 4], 1));
+        // Shares smaller than a lamport are refunded entirely
+        assert_eq!(split_fee(1, &[1, 1]), (vec![0, 0], 1));
+    }
+
+    #[test]
+    fn test_split_fee_zero_weights() {
+        assert_eq!(split_fee(1_000, &[0, 0]), (vec![0, 0], 1_000));
+        assert_eq!(split_fee(1_000, &[]), (vec![], 1_000));
+        assert_eq!(split_fee(1_000, &[0, 3]), (vec![0, 1_000], 0));
+        assert_eq!(split_fee(0, &[1, 2]), (vec![0, 0], 0));
+    }
+
+    #[test]
+    fn test_split_fee_never_overpays() {
+        let weights = [u8::MAX as u64 * 2, 1, 17, 254, 3];
+        for fee in [0, 1, 999, 1_000_000_007, u64::MAX] {
+            let (shares, remainder) = split_fee(fee, &weights);
+            assert_eq!(shares.iter().map(|share| *share as u128).sum::<u128>() + remainder as u128, fee as u128);
+            // Rounding down loses strictly less than one lamport per share
+            assert!(remainder < weights.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_split_operator_share() {
+        assert_eq!(split_operator_share(1_000, 0), (1_000, 0));
+        assert_eq!(split_operator_share(1_000, 2_500), (750, 250));
+        assert_eq!(split_operator_share(1_000, BPS_DENOMINATOR), (0, 1_000));
+        // 7 * 33.33% = 2.33, the operator's part is rounded down
+        assert_eq!(split_operator_share(7, 3_333), (5, 2));
+        assert_eq!(split_operator_share(u64::MAX, BPS_DENOMINATOR), (0, u64::MAX));
+    }
+
+    #[test]
+    fn test_payout_weight() {
+        assert_eq!(payout_weight(10, Outcome::Reward), 20);
+        assert_eq!(payout_weight(10, Outcome::Neutral), 10);
+        assert_eq!(payout_weight(10, Outcome::Penalty), 0);
+        assert_eq!(payout_weight(0, Outcome::Reward), 0);
+    }
+}
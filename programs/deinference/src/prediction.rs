@@ -0,0 +1,466 @@
+use anchor_lang::prelude::*;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use crate::reputation::LossFunction;
+
+// Losses are reported in millionths of a prediction unit (absolute error) or of the aggregate (relative error)
+pub const LOSS_SCALE: u64 = 1_000_000;
+
+// Largest supported number of decimal places for fixed-point predictions (10^18 fits in an i64)
+pub const MAX_FIXED_POINT_SCALE: u8 = 18;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PredictionEncoding {
+    Float32,                  // big-endian f32
+    FixedPoint { scale: u8 }, // big-endian i64 holding value * 10^scale
+}
+
+impl PredictionEncoding {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            PredictionEncoding::Float32 => true,
+            PredictionEncoding::FixedPoint { scale } => *scale <= MAX_FIXED_POINT_SCALE,
+        }
+    }
+
+    // Number of decimal places of a fixed-point encoding, 0 for floats
+    pub fn scale(&self) -> u8 {
+        match self {
+            PredictionEncoding::Float32 => 0,
+            PredictionEncoding::FixedPoint { scale } => *scale,
+        }
+    }
+
+    // Encoded size of a single prediction element
+    pub fn element_size(&self) -> usize {
+        match self {
+            PredictionEncoding::Float32 => f32::SIZE,
+            PredictionEncoding::FixedPoint { .. } => i64::SIZE,
+        }
+    }
+}
+
+// Decodes `len` consecutive elements, rejecting any other length
+pub fn decode_vector<T: Sample>(bytes: &[u8], len: usize) -> Option<Vec<T>> {
+    if bytes.len() != len * T::SIZE {
+        return None
+    }
+    bytes.chunks_exact(T::SIZE).map(T::decode).collect()
+}
+
+pub fn encode_vector<T: Sample>(values: &[T]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.encode()).collect()
+}
+
+// Largest per-coordinate loss, so a vector is only within tolerance if every coordinate is
+pub fn vector_loss<T: Sample>(prediction: &[T], aggregate: &[T], function: LossFunction, scale: u8) -> u64 {
+    prediction
+        .iter()
+        .zip(aggregate)
+        .map(|(value, aggregate)| value.loss(aggregate, function, scale))
+        .max()
+        .unwrap_or(0)
+}
+
+// Maximum number of Weiszfeld iterations for the geometric median
+const GEOMETRIC_MEDIAN_ITERATIONS: usize = 64;
+
+// Fixed-point arithmetic used by the integer Weiszfeld iteration
+const WEISZFELD_ONE: i128 = 1 << 32;
+
+// A scalar prediction value that can be aggregated and scored.
+// Implemented for f32 (Float32 tasks) and i64 (FixedPoint tasks, integer arithmetic only).
+pub trait Sample: Copy + PartialEq + Debug {
+    const SIZE: usize;
+
+    fn decode(bytes: &[u8]) -> Option<Self>;
+    fn encode(&self) -> Vec<u8>;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+    // Reputation-weighted mean, None if the total reputation is zero
+    fn weighted_mean(pairs: &[(Self, u32)]) -> Option<Self>;
+    // Distance to the aggregate in units of LOSS_SCALE, `scale` is the fixed-point scale of the task
+    fn loss(&self, aggregate: &Self, function: LossFunction, scale: u8) -> u64;
+    // Non-negative entries summing to one (10^scale for fixed-point), up to rounding
+    fn is_distribution(values: &[Self], scale: u8) -> bool;
+    // Rescales non-negative entries to sum to one, None if they sum to zero
+    fn normalize(values: &[Self], scale: u8) -> Option<Vec<Self>>;
+    // Reputation-weighted geometric median (the point minimizing the weighted sum of
+    // Euclidean distances), approximated with a bounded number of Weiszfeld iterations
+    fn geometric_median(points: &[(Vec<Self>, u32)]) -> Option<Vec<Self>>;
+}
+
+// Reputation-weighted coordinate-wise mean, the starting point of the Weiszfeld iteration
+fn coordinate_mean<T: Sample>(points: &[(Vec<T>, u32)]) -> Option<Vec<T>> {
+    let len = points.first()?.0.len();
+    (0..len)
+        .map(|coordinate| {
+            let column: Vec<(T, u32)> = points.iter().map(|(point, reputation)| (point[coordinate], *reputation)).collect();
+            T::weighted_mean(&column)
+        })
+        .collect()
+}
+
+impl Sample for f32 {
+    const SIZE: usize = 4;
+
+    // Exactly 4 bytes, NaN and infinities are rejected
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 4] = bytes.try_into().ok()?;
+        let value = f32::from_be_bytes(bytes);
+        value.is_finite().then_some(value)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+
+    fn weighted_mean(pairs: &[(Self, u32)]) -> Option<Self> {
+        let total_reputation: u64 = pairs.iter().map(|(_, reputation)| *reputation as u64).sum();
+        if total_reputation == 0 {
+            return None
+        }
+
+        // Accumulate in f64 so large reputations do not lose precision
+        let weighted_sum: f64 = pairs
+            .iter()
+            .map(|(prediction, reputation)| *prediction as f64 * *reputation as f64)
+            .sum();
+
+        Some((weighted_sum / total_reputation as f64) as f32)
+    }
+
+    fn loss(&self, aggregate: &Self, function: LossFunction, _scale: u8) -> u64 {
+        let error = (*self as f64 - *aggregate as f64).abs();
+        let loss = match function {
+            LossFunction::RelativeError if *aggregate != 0.0 => error / (*aggregate as f64).abs(),
+            _ => error,
+        };
+        // Float to int casts saturate
+        (loss * LOSS_SCALE as f64) as u64
+    }
+
+    fn is_distribution(values: &[Self], _scale: u8) -> bool {
+        let sum: f64 = values.iter().map(|value| *value as f64).sum();
+        values.iter().all(|value| *value >= 0.0) && (sum - 1.0).abs() <= 1e-4 * values.len() as f64
+    }
+
+    fn normalize(values: &[Self], _scale: u8) -> Option<Vec<Self>> {
+        let sum: f64 = values.iter().map(|value| *value as f64).sum();
+        if sum <= 0.0 {
+            return None
+        }
+        Some(values.iter().map(|value| (*value as f64 / sum) as f32).collect())
+    }
+
+    // Iterates in f64, which only uses correctly rounded IEEE-754 operations (including sqrt)
+    fn geometric_median(points: &[(Vec<Self>, u32)]) -> Option<Vec<Self>> {
+        let points: Vec<(Vec<f32>, u32)> = points.iter().filter(|(_, reputation)| *reputation > 0).cloned().collect();
+        let mut estimate: Vec<f64> = coordinate_mean(&points)?.iter().map(|value| *value as f64).collect();
+        let points: Vec<(Vec<f64>, f64)> = points
+            .iter()
+            .map(|(point, reputation)| (point.iter().map(|value| *value as f64).collect(), *reputation as f64))
+            .collect();
+
+        for _ in 0..GEOMETRIC_MEDIAN_ITERATIONS {
+            let mut numerator = vec![0.0f64; estimate.len()];
+            let mut denominator = 0.0f64;
+            for (point, reputation) in &points {
+                let distance = point.iter().zip(&estimate).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt();
+                if distance == 0.0 {
+                    // The estimate sits on a data point, which is where the iteration converges
+                    return Some(estimate.iter().map(|value| *value as f32).collect());
+                }
+                for (sum, x) in numerator.iter_mut().zip(point) {
+                    *sum += reputation * x / distance;
+                }
+                denominator += reputation / distance;
+            }
+
+            let next: Vec<f64> = numerator.iter().map(|sum| sum / denominator).collect();
+            if next == estimate {
+                break;
+            }
+            estimate = next;
+        }
+
+        Some(estimate.iter().map(|value| *value as f32).collect())
+    }
+}
+
+impl Sample for i64 {
+    const SIZE: usize = 8;
+
+    // Exactly 8 bytes
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 8] = bytes.try_into().ok()?;
+        Some(i64::from_be_bytes(bytes))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+
+    // Rounds towards negative infinity
+    fn weighted_mean(pairs: &[(Self, u32)]) -> Option<Self> {
+        let total_reputation: i128 = pairs.iter().map(|(_, reputation)| *reputation as i128).sum();
+        if total_reputation == 0 {
+            return None
+        }
+
+        let weighted_sum: i128 = pairs
+            .iter()
+            .map(|(prediction, reputation)| *prediction as i128 * *reputation as i128)
+            .sum();
+
+        // A weighted mean always lies within the range of its inputs, so it fits back into an i64
+        Some(weighted_sum.div_euclid(total_reputation) as i64)
+    }
+
+    // Truncates towards zero
+    fn loss(&self, aggregate: &Self, function: LossFunction, scale: u8) -> u64 {
+        let error = (*self as i128 - *aggregate as i128).unsigned_abs();
+        let loss = match function {
+            LossFunction::RelativeError if *aggregate != 0 => {
+                error * LOSS_SCALE as u128 / aggregate.unsigned_abs() as u128
+            },
+            _ => error * LOSS_SCALE as u128 / 10u128.pow(scale as u32),
+        };
+        loss.min(u64::MAX as u128) as u64
+    }
+
+    // Each entry may be off by one raw unit of rounding
+    fn is_distribution(values: &[Self], scale: u8) -> bool {
+        let one = 10i128.pow(scale as u32);
+        let sum: i128 = values.iter().map(|value| *value as i128).sum();
+        values.iter().all(|value| *value >= 0) && (sum - one).abs() <= values.len() as i128
+    }
+
+    // Entries are rounded down and the leftover raw units go to the first entries, so the result
+    // sums to exactly 10^scale
+    fn normalize(values: &[Self], scale: u8) -> Option<Vec<Self>> {
+        let one = 10i128.pow(scale as u32);
+        let sum: i128 = values.iter().map(|value| *value as i128).sum();
+        if sum <= 0 {
+            return None
+        }
+
+        let mut normalized: Vec<i64> = values.iter().map(|value| (*value as i128 * one / sum) as i64).collect();
+        let mut leftover = one - normalized.iter().map(|value| *value as i128).sum::<i128>();
+        for value in normalized.iter_mut() {
+            if leftover == 0 {
+                break;
+            }
+            *value += 1;
+            leftover -= 1;
+        }
+        Some(normalized)
+    }
+
+    // Integer-only Weiszfeld iteration: reciprocal distances are represented with WEISZFELD_ONE
+    // as one and rounded down, and squared distances saturate for points more than ~2^63 apart
+    fn geometric_median(points: &[(Vec<Self>, u32)]) -> Option<Vec<Self>> {
+        let points: Vec<(Vec<i64>, u32)> = points.iter().filter(|(_, reputation)| *reputation > 0).cloned().collect();
+        let mut estimate = coordinate_mean(&points)?;
+
+        for _ in 0..GEOMETRIC_MEDIAN_ITERATIONS {
+            let mut numerator = vec![0i128; estimate.len()];
+            let mut denominator = 0i128;
+            for (point, reputation) in &points {
+                let squared_distance = point
+                    .iter()
+                    .zip(&estimate)
+                    .map(|(x, y)| (*x as i128 - *y as i128).unsigned_abs())
+                    .fold(0u128, |sum, difference| sum.saturating_add(difference.saturating_mul(difference)));
+                let distance = isqrt(squared_distance) as i128;
+                if distance == 0 {
+                    // The estimate sits on a data point, which is where the iteration converges
+                    return Some(estimate);
+                }
+                let weight = *reputation as i128 * WEISZFELD_ONE / distance;
+                for (sum, x) in numerator.iter_mut().zip(point) {
+                    *sum = sum.saturating_add(weight.saturating_mul(*x as i128));
+                }
+                denominator += weight;
+            }
+
+            if denominator == 0 {
+                break;
+            }
+            // Each coordinate is a weighted mean of the points, so it fits back into an i64
+            let next: Vec<i64> = numerator.iter().map(|sum| sum.div_euclid(denominator) as i64).collect();
+            if next == estimate {
+                break;
+            }
+            estimate = next;
+        }
+
+        Some(estimate)
+    }
+}
+
+// Largest integer whose square does not exceed `value`
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value
+    }
+    // Newton's method from an initial guess that is never below the root
+    let mut x = 1u128 << (128 - value.leading_zeros()).div_ceil(2);
+    loop {
+        let y = (x + value / x) / 2;
+        if y >= x {
+            return x
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_f32() {
+        assert_eq!(f32::decode(&12.25f32.to_be_bytes()), Some(12.25));
+        assert_eq!(f32::decode(&(-0.5f32).to_be_bytes()), Some(-0.5));
+
+        // Wrong lengths
+        assert_eq!(f32::decode(&[]), None);
+        assert_eq!(f32::decode(&[0, 0, 0]), None);
+        assert_eq!(f32::decode(&[0, 0, 0, 0, 0]), None);
+
+        // Non-finite values
+        assert_eq!(f32::decode(&f32::NAN.to_be_bytes()), None);
+        assert_eq!(f32::decode(&f32::INFINITY.to_be_bytes()), None);
+        assert_eq!(f32::decode(&f32::NEG_INFINITY.to_be_bytes()), None);
+    }
+
+    #[test]
+    fn test_decode_i64() {
+        assert_eq!(i64::decode(&1225i64.to_be_bytes()), Some(1225));
+        assert_eq!(i64::decode(&i64::MIN.to_be_bytes()), Some(i64::MIN));
+        assert_eq!(i64::decode(&12.25f32.to_be_bytes()), None);
+        assert_eq!(i64::decode(&[0; 9]), None);
+    }
+
+    #[test]
+    fn test_encoding_validation() {
+        assert!(PredictionEncoding::Float32.is_valid());
+        assert!(PredictionEncoding::FixedPoint { scale: MAX_FIXED_POINT_SCALE }.is_valid());
+        assert!(!PredictionEncoding::FixedPoint { scale: MAX_FIXED_POINT_SCALE + 1 }.is_valid());
+    }
+
+    #[test]
+    fn test_decode_vector() {
+        let bytes = encode_vector(&[1.0f32, 2.0, 3.0]);
+        assert_eq!(decode_vector::<f32>(&bytes, 3), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(decode_vector::<f32>(&bytes, 2), None);
+        assert_eq!(decode_vector::<i64>(&bytes, 3), None);
+        assert_eq!(decode_vector::<i64>(&encode_vector(&[-1i64, 7]), 2), Some(vec![-1, 7]));
+
+        // A single non-finite entry invalidates the whole vector
+        let bytes = encode_vector(&[1.0f32, f32::NAN]);
+        assert_eq!(decode_vector::<f32>(&bytes, 2), None);
+    }
+
+    #[test]
+    fn test_vector_loss_is_largest_coordinate_loss() {
+        assert_eq!(vector_loss(&[1.0f32, 5.0], &[1.5, 3.0], LossFunction::AbsoluteError, 0), 2 * LOSS_SCALE);
+        assert_eq!(vector_loss(&[100i64, 250], &[100, 200], LossFunction::RelativeError, 2), LOSS_SCALE / 4);
+    }
+
+    #[test]
+    fn test_distributions() {
+        assert!(f32::is_distribution(&[0.25, 0.75], 0));
+        assert!(!f32::is_distribution(&[0.5, 0.75], 0));
+        assert!(!f32::is_distribution(&[-0.25, 1.25], 0));
+        assert_eq!(f32::normalize(&[1.0, 3.0], 0), Some(vec![0.25, 0.75]));
+        assert_eq!(f32::normalize(&[0.0, 0.0], 0), None);
+
+        assert!(i64::is_distribution(&[2_500, 7_500], 4));
+        assert!(i64::is_distribution(&[3_333, 3_333, 3_333], 4));
+        assert!(!i64::is_distribution(&[3_000, 3_000, 3_000], 4));
+        assert!(!i64::is_distribution(&[-1, 10_001], 4));
+
+        // Leftover units from rounding down go to the first entries
+        assert_eq!(i64::normalize(&[1, 1, 1], 4), Some(vec![3_334, 3_333, 3_333]));
+        assert_eq!(i64::normalize(&[1, 3], 2), Some(vec![25, 75]));
+        assert_eq!(i64::normalize(&[0, 0], 2), None);
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for value in 0u128..1_000 {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value);
+        }
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(isqrt((u64::MAX as u128) * (u64::MAX as u128)), u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_geometric_median() {
+        // Three collinear points, the geometric median is the middle one
+        let points = vec![(vec![0.0f32, 0.0], 1), (vec![1.0, 1.0], 1), (vec![10.0, 10.0], 1)];
+        let median = f32::geometric_median(&points).unwrap();
+        assert!((median[0] - 1.0).abs() < 1e-3 && (median[1] - 1.0).abs() < 1e-3);
+
+        // Unlike the mean, a single outlier barely moves it
+        let points = vec![
+            (vec![0i64, 0], 1),
+            (vec![0, 1_000], 1),
+            (vec![1_000, 0], 1),
+            (vec![1_000, 1_000], 1),
+            (vec![1_000_000, 1_000_000], 1),
+        ];
+        let median = i64::geometric_median(&points).unwrap();
+        assert!(median.iter().all(|value| (500..=1_100).contains(value)), "{:?}", median);
+
+        // A dominant reputation pulls it onto that point
+        let points = vec![(vec![0i64, 0], 10), (vec![100, 0], 1), (vec![0, 100], 1)];
+        assert_eq!(i64::geometric_median(&points), Some(vec![0, 0]));
+
+        assert_eq!(i64::geometric_median(&[(vec![5, 5], 0)]), None);
+        assert_eq!(f32::geometric_median(&[]), None);
+    }
+
+    #[test]
+    fn test_fixed_point_weighted_mean_rounding() {
+        assert_eq!(i64::weighted_mean(&[(100, 1), (200, 1), (400, 2)]), Some(275));
+        // 10 / 3 and -10 / 3 both round down
+        assert_eq!(i64::weighted_mean(&[(10, 1), (0, 2)]), Some(3));
+        assert_eq!(i64::weighted_mean(&[(-10, 1), (0, 2)]), Some(-4));
+        // No overflow on extreme values
+        assert_eq!(i64::weighted_mean(&[(i64::MAX, 255), (i64::MAX, 255)]), Some(i64::MAX));
+        assert_eq!(i64::weighted_mean(&[(i64::MIN, 255), (i64::MAX, 0)]), Some(i64::MIN));
+        assert_eq!(i64::weighted_mean(&[(5, 0)]), None);
+    }
+
+    #[test]
+    fn test_fixed_point_loss() {
+        // 12.00 vs 10.00 at scale 2 is an absolute error of 2.0
+        assert_eq!(1200i64.loss(&1000, LossFunction::AbsoluteError, 2), 2 * LOSS_SCALE);
+        // and a relative error of 20%
+        assert_eq!(1200i64.loss(&1000, LossFunction::RelativeError, 2), LOSS_SCALE / 5);
+        assert_eq!((-1200i64).loss(&-1000, LossFunction::RelativeError, 2), LOSS_SCALE / 5);
+        // Relative error against zero falls back to absolute error
+        assert_eq!(25i64.loss(&0, LossFunction::RelativeError, 2), LOSS_SCALE / 4);
+        // Extreme distances saturate instead of overflowing
+        assert_eq!(i64::MAX.loss(&i64::MIN, LossFunction::AbsoluteError, 0), u64::MAX);
+    }
+
+    #[test]
+    fn test_float_loss() {
+        assert_eq!(12.0f32.loss(&10.0, LossFunction::AbsoluteError, 0), 2 * LOSS_SCALE);
+        assert_eq!(8.0f32.loss(&10.0, LossFunction::AbsoluteError, 0), 2 * LOSS_SCALE);
+        assert_eq!(12.0f32.loss(&10.0, LossFunction::RelativeError, 0), LOSS_SCALE / 5);
+        assert_eq!(0.25f32.loss(&0.0, LossFunction::RelativeError, 0), LOSS_SCALE / 4);
+    }
+}
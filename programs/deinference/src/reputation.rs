@@ -0,0 +1,229 @@
+use anchor_lang::prelude::*;
+use crate::prediction::{decode_vector, vector_loss, Sample};
+use crate::state::{ModelData, ResultEntry};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LossFunction {
+    AbsoluteError,  // |prediction - aggregate|
+    RelativeError,  // |prediction - aggregate| / |aggregate|, falls back to absolute when aggregate is 0
+}
+
+// Tolerance bands are expressed in units of the chosen loss, scaled by LOSS_SCALE:
+// loss <= tolerance is rewarded, loss > penalty_threshold is penalized, anything in between is left unchanged
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReputationPolicy {
+    pub loss: LossFunction,
+    pub tolerance: u64,
+    pub penalty_threshold: u64,
+    pub reward: u8,
+    pub penalty: u8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Reward,
+    Neutral,
+    Penalty,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReputationChange {
+    pub weights_hash: [u8; 32],
+    pub outcome: Outcome,
+    pub old_reputation: u8,
+    pub new_reputation: u8,
+    pub loss: Option<u64>, // None if the prediction could not be decoded
+}
+
+impl ReputationPolicy {
+    pub fn outcome(&self, loss: u64) -> Outcome {
+        if loss <= self.tolerance {
+            Outcome::Reward
+        } else if loss <= self.penalty_threshold {
+            Outcome::Neutral
+        } else {
+            Outcome::Penalty
+        }
+    }
+
+    // Reputation saturates at 0 and u8::MAX
+    pub fn apply(&self, reputation: u8, outcome: Outcome) -> u8 {
+        match outcome {
+            Outcome::Reward => reputation.saturating_add(self.reward),
+            Outcome::Neutral => reputation,
+            Outcome::Penalty => reputation.saturating_sub(self.penalty),
+        }
+    }
+}
+
+// Scores every result against the aggregate and updates the reputation of the model that produced it.
+// Results without a registered model are skipped, undecodable predictions are penalized.
+// Scalar aggregates are passed as a single element, `scale` is the fixed-point scale of the task's prediction encoding.
+pub fn update_reputations<T: Sample>(
+    policy: &ReputationPolicy,
+    results: &[ResultEntry],
+    models: &mut [ModelData],
+    aggregate: &[T],
+    scale: u8
+) -> Vec<ReputationChange> {
+    let mut changes = Vec::with_capacity(results.len());
+
+    for result in results {
+        let Some(model) = models.iter_mut().find(|model| model.weights_hash == result.weights_hash) else {
+            continue;
+        };
+
+        let loss = decode_vector::<T>(&result.prediction, aggregate.len())
+            .map(|prediction| vector_loss(&prediction, aggregate, policy.loss, scale));
+        let outcome = loss.map_or(Outcome::Penalty, |loss| policy.outcome(loss));
+
+        let old_reputation = model.reputation;
+        model.reputation = policy.apply(old_reputation, outcome);
+
+        changes.push(ReputationChange {
+            weights_hash: model.weights_hash,
+            outcome,
+            old_reputation,
+            new_reputation: model.reputation,
+            loss,
+        });
+    }
+
+    changes
+}
+
+// Commits never revealed are left out of the aggregate and penalized as if their prediction could not be decoded
+pub fn penalize_unrevealed(
+    policy: &ReputationPolicy,
+    unrevealed: &[ResultEntry],
+    models: &mut [ModelData]
+) -> Vec<ReputationChange> {
+    unrevealed
+        .iter()
+        .filter_map(|result| {
+            let model = models.iter_mut().find(|model| model.weights_hash == result.weights_hash)?;
+            let old_reputation = model.reputation;
+            model.reputation = policy.apply(old_reputation, Outcome::Penalty);
+            Some(ReputationChange {
+                weights_hash: model.weights_hash,
+                outcome: Outcome::Penalty,
+                old_reputation,
+                new_reputation: model.reputation,
+                loss: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prediction::LOSS_SCALE;
+    use crate::state::fixtures::{committed_result, encoded_result, model, result};
+
+    fn policy(loss: LossFunction) -> ReputationPolicy {
+        ReputationPolicy {
+            loss,
+            tolerance: LOSS_SCALE / 2,
+            penalty_threshold: LOSS_SCALE,
+            reward: 2,
+            penalty: 3,
+        }
+    }
+
+    #[test]
+    fn test_tolerance_bands() {
+        let policy = policy(LossFunction::AbsoluteError);
+        assert_eq!(policy.outcome(0), Outcome::Reward);
+        assert_eq!(policy.outcome(LOSS_SCALE / 2), Outcome::Reward);
+        assert_eq!(policy.outcome(LOSS_SCALE / 2 + 1), Outcome::Neutral);
+        assert_eq!(policy.outcome(LOSS_SCALE), Outcome::Neutral);
+        assert_eq!(policy.outcome(LOSS_SCALE + 1), Outcome::Penalty);
+        assert_eq!(policy.outcome(u64::MAX), Outcome::Penalty);
+    }
+
+    #[test]
+    fn test_saturation() {
+        let policy = policy(LossFunction::AbsoluteError);
+        assert_eq!(policy.apply(10, Outcome::Reward), 12);
+        assert_eq!(policy.apply(u8::MAX - 1, Outcome::Reward), u8::MAX);
+        assert_eq!(policy.apply(10, Outcome::Neutral), 10);
+        assert_eq!(policy.apply(10, Outcome::Penalty), 7);
+        assert_eq!(policy.apply(2, Outcome::Penalty), 0);
+    }
+
+    #[test]
+    fn test_update_reputations() {
+        let policy = policy(LossFunction::AbsoluteError);
+        let mut models = vec![model(1, 5), model(2, 5), model(3, 5), model(4, 5)];
+        let results = vec![
+            result(1, 10.25), // within tolerance
+            result(2, 10.75), // neutral band
+            result(3, 12.0),  // outside penalty threshold
+            result(9, 10.0), // unregistered model
+        ];
+
+        let changes = update_reputations(&policy, &results, &mut models, &[10.0f32], 0);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(models[0].reputation, 7);
+        assert_eq!(models[1].reputation, 5);
+        assert_eq!(models[2].reputation, 2);
+        // Models without a submission are untouched
+        assert_eq!(models[3].reputation, 5);
+
+        assert_eq!(changes[2].old_reputation, 5);
+        assert_eq!(changes[2].new_reputation, 2);
+        assert_eq!(changes[2].loss, Some(2 * LOSS_SCALE));
+    }
+
+    #[test]
+    fn test_update_reputations_fixed_point() {
+        let policy = policy(LossFunction::RelativeError);
+        let mut models = vec![model(1, 5), model(2, 5), model(3, 5)];
+        let fixed = |id: u8, prediction: i64| encoded_result(id, prediction.to_be_bytes().to_vec());
+        let results = vec![
+            fixed(1, 1_400), // 40% off
+            fixed(2, 1_750), // 75% off
+            fixed(3, 2_500), // 150% off
+        ];
+
+        let changes = update_reputations(&policy, &results, &mut models, &[1_000i64], 2);
+
+        assert_eq!(models.iter().map(|model| model.reputation).collect::<Vec<_>>(), vec![7, 5, 2]);
+        assert_eq!(changes[0].loss, Some(400_000));
+        assert_eq!(changes[1].loss, Some(750_000));
+        assert_eq!(changes[2].loss, Some(1_500_000));
+    }
+
+    #[test]
+    fn test_undecodable_prediction_is_penalized() {
+        let policy = policy(LossFunction::RelativeError);
+        let mut models = vec![model(1, 5)];
+        let results = vec![encoded_result(1, vec![1, 2, 3])];
+
+        let changes = update_reputations(&policy, &results, &mut models, &[10.0f32], 0);
+
+        assert_eq!(models[0].reputation, 2);
+        assert_eq!(changes[0].loss, None);
+    }
+
+    #[test]
+    fn test_unrevealed_commits_are_penalized() {
+        let policy = policy(LossFunction::AbsoluteError);
+        let mut models = vec![model(1, 5), model(2, 1)];
+        let unrevealed = vec![
+            committed_result(2),
+            committed_result(9), // retired model
+        ];
+
+        let changes = penalize_unrevealed(&policy, &unrevealed, &mut models);
+
+        // Only the committing model is penalized, saturating at 0
+        assert_eq!(models[0].reputation, 5);
+        assert_eq!(models[1].reputation, 0);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].outcome, Outcome::Penalty);
+        assert_eq!(changes[0].loss, None);
+    }
+}
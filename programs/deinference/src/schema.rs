@@ -0,0 +1,306 @@
+use anchor_lang::prelude::*;
+use crate::error::Errors;
+use crate::prediction::{decode_vector, PredictionEncoding, Sample};
+
+// Maximum number of input fields a task schema may declare
+pub const MAX_INPUT_FIELDS: usize = 8;
+
+// Maximum number of elements in a vector or distribution output
+pub const MAX_OUTPUT_LEN: u16 = 64;
+
+// Input fields are Borsh encoded, in declaration order, with nothing after the last field
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FieldType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    String, // u32 length prefix followed by UTF-8 bytes
+}
+
+// Shape of a prediction, elements use the task's prediction encoding and are concatenated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputKind {
+    Scalar,
+    Vector { len: u16 },          // e.g. an embedding
+    Distribution { classes: u16 }, // class probabilities, non-negative and summing to one
+}
+
+// Inclusive bounds on predictions, in the units of the task's prediction encoding.
+// Vector outputs are bounded element-wise.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum OutputRange {
+    Float32 { lower: f32, upper: f32 },
+    FixedPoint { lower: i64, upper: i64 }, // raw values, i.e. already multiplied by 10^scale
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub struct OutputSchema {
+    pub kind: OutputKind,
+    pub encoding: PredictionEncoding,
+    pub range: Option<OutputRange>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Debug)]
+pub struct TaskSchema {
+    pub inputs: Vec<FieldType>,
+    pub output: OutputSchema,
+}
+
+impl TaskSchema {
+    // space = vec_size (4) + max_input_fields (8) * field_type (1) + kind (3) + encoding (2) + range (1 + 1 + 16)
+    pub const SPACE: usize = 4 + MAX_INPUT_FIELDS + 3 + 2 + 18;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.inputs.len() <= MAX_INPUT_FIELDS, Errors::InvalidTaskSchema);
+        self.output.validate()
+    }
+
+    pub fn validate_input(&self, input_data: &[u8]) -> Result<()> {
+        let mut remaining = input_data;
+        for field in &self.inputs {
+            remaining = field.consume(remaining).ok_or(error!(Errors::InvalidInputData))?;
+        }
+        require!(remaining.is_empty(), Errors::InvalidInputData);
+        Ok(())
+    }
+}
+
+impl FieldType {
+    // Returns the bytes left after this field, or None if the field is missing or malformed
+    fn consume<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let size = match self {
+            FieldType::Bool => {
+                return match data.split_first()? {
+                    (0 | 1, rest) => Some(rest),
+                    _ => None,
+                }
+            },
+            FieldType::String => {
+                let length = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+                let rest = &data[4..];
+                if rest.len() < length {
+                    return None
+                }
+                let (string, rest) = rest.split_at(length);
+                std::str::from_utf8(string).ok()?;
+                return Some(rest)
+            },
+            FieldType::U8 | FieldType::I8 => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => 4,
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => 8,
+        };
+        data.get(size..)
+    }
+}
+
+impl OutputKind {
+    // Number of elements in a prediction
+    pub fn len(&self) -> usize {
+        match self {
+            OutputKind::Scalar => 1,
+            OutputKind::Vector { len } => *len as usize,
+            OutputKind::Distribution { classes } => *classes as usize,
+        }
+    }
+}
+
+impl OutputSchema {
+    // Encoded size of a single prediction
+    pub fn prediction_size(&self) -> usize {
+        self.kind.len() * self.encoding.element_size()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.encoding.is_valid(), Errors::InvalidPredictionEncoding);
+        match self.kind {
+            OutputKind::Scalar => {},
+            OutputKind::Vector { len } => {
+                require!(len > 0 && len <= MAX_OUTPUT_LEN, Errors::InvalidTaskSchema);
+            },
+            // Distributions are implicitly bounded by [0, 1]
+            OutputKind::Distribution { classes } => {
+                require!(classes > 1 && classes <= MAX_OUTPUT_LEN, Errors::InvalidTaskSchema);
+                require!(self.range.is_none(), Errors::InvalidTaskSchema);
+            },
+        }
+        match (self.encoding, self.range) {
+            (_, None) => {},
+            (PredictionEncoding::Float32, Some(OutputRange::Float32 { lower, upper })) => {
+                require!(lower.is_finite() && upper.is_finite() && lower <= upper, Errors::InvalidTaskSchema);
+            },
+            (PredictionEncoding::FixedPoint { .. }, Some(OutputRange::FixedPoint { lower, upper })) => {
+                require!(lower <= upper, Errors::InvalidTaskSchema);
+            },
+            _ => return err!(Errors::InvalidTaskSchema),
+        }
+        Ok(())
+    }
+
+    pub fn validate_prediction(&self, prediction: &[u8]) -> Result<()> {
+        match (self.encoding, self.range) {
+            (PredictionEncoding::Float32, Some(OutputRange::Float32 { lower, upper })) => {
+                self.validate_values::<f32>(prediction, |value| lower <= value && value <= upper)
+            },
+            (PredictionEncoding::FixedPoint { .. }, Some(OutputRange::FixedPoint { lower, upper })) => {
+                self.validate_values::<i64>(prediction, |value| lower <= value && value <= upper)
+            },
+            (PredictionEncoding::Float32, _) => self.validate_values::<f32>(prediction, |_| true),
+            (PredictionEncoding::FixedPoint { .. }, _) => self.validate_values::<i64>(prediction, |_| true),
+        }
+    }
+
+    fn validate_values<T: Sample>(&self, prediction: &[u8], in_range: impl Fn(T) -> bool) -> Result<()> {
+        let values: Vec<T> = decode_vector(prediction, self.kind.len()).ok_or(error!(Errors::InvalidPrediction))?;
+        require!(values.iter().all(|value| in_range(*value)), Errors::PredictionOutOfRange);
+        if let OutputKind::Distribution { .. } = self.kind {
+            require!(T::is_distribution(&values, self.encoding.scale()), Errors::PredictionOutOfRange);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prediction::encode_vector;
+
+    // Mirrors example_task.json
+    fn example_schema() -> TaskSchema {
+        TaskSchema {
+            inputs: vec![FieldType::U32, FieldType::U32],
+            output: OutputSchema {
+                kind: OutputKind::Scalar,
+                encoding: PredictionEncoding::Float32,
+                range: Some(OutputRange::Float32 { lower: 0.0, upper: 1.0 }),
+            },
+        }
+    }
+
+    #[test]
+    fn test_validate_input() {
+        let schema = example_schema();
+        let input = [1234u32.to_le_bytes(), 5678u32.to_le_bytes()].concat();
+        assert!(schema.validate_input(&input).is_ok());
+
+        // Missing and trailing bytes
+        assert!(schema.validate_input(&input[..7]).is_err());
+        assert!(schema.validate_input(&[input.as_slice(), &[0]].concat()).is_err());
+        assert!(schema.validate_input(&[]).is_err());
+    }
+
+    #[test]
+    fn test_validate_input_strings_and_bools() {
+        let schema = TaskSchema {
+            inputs: vec![FieldType::Bool, FieldType::String, FieldType::F64],
+            output: example_schema().output,
+        };
+        let input = |flag: u8, string: &[u8]| {
+            [vec![flag], (string.len() as u32).to_le_bytes().to_vec(), string.to_vec(), 1.5f64.to_le_bytes().to_vec()].concat()
+        };
+
+        assert!(schema.validate_input(&input(1, b"test")).is_ok());
+        assert!(schema.validate_input(&input(0, b"")).is_ok());
+        assert!(schema.validate_input(&input(2, b"test")).is_err());
+        assert!(schema.validate_input(&input(1, &[0xff, 0xfe])).is_err());
+
+        // String length prefix pointing past the end of the data
+        let mut truncated = input(1, b"test");
+        truncated[1] = 200;
+        assert!(schema.validate_input(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_validate_prediction_range() {
+        let output = example_schema().output;
+        assert!(output.validate_prediction(&0.0f32.to_be_bytes()).is_ok());
+        assert!(output.validate_prediction(&0.5f32.to_be_bytes()).is_ok());
+        assert!(output.validate_prediction(&1.0f32.to_be_bytes()).is_ok());
+        assert_eq!(
+            output.validate_prediction(&1.5f32.to_be_bytes()).unwrap_err(),
+            error!(Errors::PredictionOutOfRange)
+        );
+        assert_eq!(
+            output.validate_prediction(&f32::NAN.to_be_bytes()).unwrap_err(),
+            error!(Errors::InvalidPrediction)
+        );
+
+        let fixed = OutputSchema {
+            kind: OutputKind::Scalar,
+            encoding: PredictionEncoding::FixedPoint { scale: 2 },
+            range: Some(OutputRange::FixedPoint { lower: -100, upper: 100 }),
+        };
+        assert!(fixed.validate_prediction(&(-100i64).to_be_bytes()).is_ok());
+        assert!(fixed.validate_prediction(&101i64.to_be_bytes()).is_err());
+        assert!(fixed.validate_prediction(&0.5f32.to_be_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        assert!(example_schema().validate().is_ok());
+
+        let mut too_many_inputs = example_schema();
+        too_many_inputs.inputs = vec![FieldType::U8; MAX_INPUT_FIELDS + 1];
+        assert!(too_many_inputs.validate().is_err());
+
+        // Range must match the encoding
+        let mismatched = OutputSchema {
+            kind: OutputKind::Scalar,
+            encoding: PredictionEncoding::FixedPoint { scale: 2 },
+            range: Some(OutputRange::Float32 { lower: 0.0, upper: 1.0 }),
+        };
+        assert!(mismatched.validate().is_err());
+
+        let inverted = OutputSchema {
+            kind: OutputKind::Scalar,
+            encoding: PredictionEncoding::Float32,
+            range: Some(OutputRange::Float32 { lower: 1.0, upper: 0.0 }),
+        };
+        assert!(inverted.validate().is_err());
+
+        let unbounded = OutputSchema { kind: OutputKind::Scalar, encoding: PredictionEncoding::Float32, range: None };
+        assert!(unbounded.validate().is_ok());
+
+        let empty_vector = OutputSchema { kind: OutputKind::Vector { len: 0 }, ..unbounded };
+        assert!(empty_vector.validate().is_err());
+        let bounded_distribution = OutputSchema {
+            kind: OutputKind::Distribution { classes: 3 },
+            ..example_schema().output
+        };
+        assert!(bounded_distribution.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_vector_predictions() {
+        let vector = OutputSchema {
+            kind: OutputKind::Vector { len: 3 },
+            encoding: PredictionEncoding::Float32,
+            range: Some(OutputRange::Float32 { lower: -1.0, upper: 1.0 }),
+        };
+        assert_eq!(vector.prediction_size(), 12);
+        assert!(vector.validate_prediction(&encode_vector(&[-1.0f32, 0.0, 1.0])).is_ok());
+        assert!(vector.validate_prediction(&encode_vector(&[-1.0f32, 0.0])).is_err());
+        assert_eq!(
+            vector.validate_prediction(&encode_vector(&[-1.0f32, 0.0, 1.5])).unwrap_err(),
+            error!(Errors::PredictionOutOfRange)
+        );
+
+        let distribution = OutputSchema {
+            kind: OutputKind::Distribution { classes: 3 },
+            encoding: PredictionEncoding::FixedPoint { scale: 4 },
+            range: None,
+        };
+        assert_eq!(distribution.prediction_size(), 24);
+        assert!(distribution.validate_prediction(&encode_vector(&[2_000i64, 3_000, 5_000])).is_ok());
+        assert!(distribution.validate_prediction(&encode_vector(&[2_000i64, 3_000, 4_000])).is_err());
+        assert!(distribution.validate_prediction(&encode_vector(&[-1_000i64, 6_000, 5_000])).is_err());
+    }
+}
@@ -1,11 +1,18 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
 use strum_macros::Display;
 use crate::error::Errors;
+use crate::instructions::AggregationAlgorithm;
+use crate::payout::BPS_DENOMINATOR;
+use crate::reputation::ReputationPolicy;
+use crate::schema::{OutputKind, TaskSchema};
 
 #[account]
 pub struct ProgramState {
-    pub creator: Pubkey, // Program's authority
+    pub admin: Pubkey, // Program's authority, set at initialization
+    pub pending_admin: Option<Pubkey>, // proposed admin, takes over once it accepts
     pub tree_count: u16,
+    pub request_count: u64, // id of the next request, every post_request takes the next one
     pub trees: Vec<TreeInfo>
 }
 
@@ -16,7 +23,15 @@ pub struct TreeInfo {
     pub current_index: u16,
 }
 
+// Maximum number of trees the program state can grow to hold
+pub const MAX_TREES: usize = 64;
+
 impl ProgramState {
+    // space = disc (8) + admin (32) + pending_admin (1 + 32) + tree_count (2) + request_count (8) + trees (4 + tree_count * (tree_address (32) + tree_config (32) + current_index (2)))
+    pub fn space(tree_count: usize) -> usize {
+        8 + 32 + 33 + 2 + 8 + (4 + tree_count * (32 + 32 + 2))
+    }
+
     pub fn get_tree(&self, target_tree_address: Pubkey) -> Option<&TreeInfo> {
         self.trees.iter().find(|tree| tree.tree_address == target_tree_address)
     }
@@ -32,38 +47,210 @@ impl ProgramState {
     }
 }
 
+// Protocol wide parameters, set at initialization and updated by the admin
+#[account]
+pub struct ProtocolConfig {
+    pub initial_reputation: u8, // reputation a model starts with when minted to a task
+    pub max_results: u16,       // results a request can hold
+    pub max_input_size: u16,    // input bytes a request can hold
+    pub min_model_count: u16,   // models a task needs before it accepts requests
+    pub protocol_fee_bps: u16,  // share of every request fee paid to the treasury
+    pub treasury: Pubkey,       // receives the protocol fee
+    pub paused: bool,           // halts requests, predictions, aggregation and minting across all tasks
+}
+
+impl ProtocolConfig {
+    // space = disc (8) + initial_reputation (1) + max_results (2) + max_input_size (2) + min_model_count (2)
+    // + protocol_fee_bps (2) + treasury (32) + paused (1)
+    pub const SPACE: usize = 8 + 1 + 2 + 2 + 2 + 2 + 32 + 1;
+
+    // Defaults a deployment starts with, sending protocol fees to `treasury`
+    pub fn with_defaults(treasury: Pubkey) -> Self {
+        ProtocolConfig {
+            initial_reputation: 1,
+            max_results: 10,
+            max_input_size: 32,
+            min_model_count: 1,
+            protocol_fee_bps: 0,
+            treasury,
+            paused: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        require!(self.initial_reputation > 0, Errors::InvalidConfig);
+        require!(self.max_results > 0 && usize::from(self.max_results) <= MAX_RESULTS, Errors::InvalidConfig);
+        require!(self.max_input_size > 0 && usize::from(self.max_input_size) <= MAX_INPUT_SIZE, Errors::InvalidConfig);
+        require!(self.min_model_count > 0, Errors::InvalidConfig);
+        require!(self.protocol_fee_bps <= MAX_PROTOCOL_FEE_BPS, Errors::InvalidConfig);
+        Ok(())
+    }
+
+    // Part of a request fee kept by the protocol, rounded down
+    pub fn protocol_fee(&self, fee: u64) -> u64 {
+        (fee as u128 * self.protocol_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+}
+
+// The protocol never keeps more than 10% of a request fee
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+
 // Account storing data about an inference task collection NFT
 #[account]
 pub struct TaskData {
     pub collection_mint: Pubkey,
-    pub model_count: u16, // num models associated with this task
-    pub models: Vec<ModelData>
+    pub owner: Pubkey, // update authority of the collection when the task was created, manages its settings
+    pub schema: TaskSchema, // expected input fields and prediction format
+    pub settings: TaskSettings, // owner controlled parameters, see update_task
+    pub fee_mint: Option<Pubkey>, // SPL mint request fees are paid in, lamports if None
+    pub model_count: u16, // num models associated with this task, each stored in its own ModelData account
+    pub paused: bool, // halts requests, predictions, aggregation and minting for this task
+}
+
+impl TaskData {
+    // space = disc (8) + collection_mint (32) + owner (32) + schema + settings + fee_mint (1 + 32) + model_count (2) + paused (1)
+    pub const SPACE: usize = 8 + 32 + 32 + TaskSchema::SPACE + TaskSettings::SPACE + 33 + 2 + 1;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TaskSettings {
+    pub algorithm: AggregationAlgorithm, // used by aggregate unless the requester picks another one
+    pub reputation_policy: ReputationPolicy, // how predictions are scored against the aggregate
+    pub min_predictions: u16, // lowest required_predictions a request can ask for
+    pub min_reputation: u8, // models below this reputation cannot submit predictions
+    pub min_fee: u64, // lowest fee a request can offer, in the task's fee mint
+    pub request_ttl: i64, // default seconds a request accepts predictions before it can be expired
+    pub commit_window: Option<i64>, // if set, predictions are committed for this many seconds after posting, then revealed
+}
+
+impl TaskSettings {
+    // space = algorithm (1 + 2) + reputation_policy (1 + 8 + 8 + 1 + 1) + min_predictions (2) + min_reputation (1)
+    // + min_fee (8) + request_ttl (8) + commit_window (1 + 8)
+    pub const SPACE: usize = 3 + 19 + 2 + 1 + 8 + 8 + 9;
+
+    // Settings must fit the task's output, the algorithm is checked against it
+    pub fn validate(&self, output: &OutputKind) -> Result<()> {
+        self.algorithm.validate(output)?;
+        require!(self.reputation_policy.tolerance <= self.reputation_policy.penalty_threshold, Errors::InvalidTaskSettings);
+        require!(self.min_predictions > 0, Errors::InvalidTaskSettings);
+        require!(self.request_ttl > 0, Errors::InvalidDeadline);
+        // The reveal window is whatever is left of the default deadline
+        if let Some(commit_window) = self.commit_window {
+            require!(commit_window > 0 && commit_window < self.request_ttl, Errors::InvalidTaskSettings);
+        }
+        Ok(())
+    }
+}
+
+// Account storing a model registered to a task, seeded by the task and the model's weights hash
+#[account]
 pub struct ModelData {
+    pub task_collection: Pubkey, // task this model is registered to
     pub weights_hash: [u8; 32], // unique identifier
     pub tree_address: Pubkey, // tree where this model is stored
     pub leaf_index: u16,
-    pub reputation: u8
+    pub reputation: u8,
+    pub owner: Pubkey, // leaf owner the model was minted to
+    pub identity: ModelIdentity, // key that attested the weights hash
+    pub operators: Vec<Pubkey>, // compute nodes the owner allows to submit predictions on its behalf
+    pub operator_share_bps: u16 // share of the model's payout kept by the operator that submitted
 }
 
-impl TaskData {
-    pub fn has_model(&self, weights_hash: &[u8; 32]) -> bool {
-        self.models.iter().any(|model| &model.weights_hash == weights_hash)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModelIdentity {
+    Ed25519, // the owner's own key signed the weights hash
+    Secp256k1 { eth_address: [u8; 20] }, // an Ethereum address signed it, and may attest predictions relayed by anyone
+}
+
+// Maximum number of operators a model owner can delegate submission rights to
+pub const MAX_OPERATORS: usize = 2;
+
+impl ModelData {
+    // space = disc (8) + task_collection (32) + weights_hash (32) + tree_address (32) + leaf_index (2) + reputation (1)
+    // + owner (32) + identity (1 + 20) + operators (4 + operator_count * 32) + operator_share_bps (2)
+    pub fn space(operator_count: usize) -> usize {
+        8 + 32 + 32 + 32 + 2 + 1 + 32 + 21 + (4 + operator_count * 32) + 2
+    }
+
+    pub fn can_submit(&self, signer: &Pubkey) -> bool {
+        &self.owner == signer || self.operators.contains(signer)
+    }
+
+    pub fn add_operator(&mut self, operator: Pubkey) -> Result<()> {
+        if !self.operators.contains(&operator) {
+            require!(self.operators.len() < MAX_OPERATORS, Errors::TooManyOperators);
+            self.operators.push(operator);
+        }
+        Ok(())
+    }
+
+    pub fn remove_operator(&mut self, operator: &Pubkey) -> Result<()> {
+        let index = self.operators
+            .iter()
+            .position(|key| key == operator)
+            .ok_or(error!(Errors::OperatorNotFound))?;
+        self.operators.remove(index);
+        Ok(())
     }
 }
 
+// Hard limits on the number of results and input bytes of a request, the configured limits must stay within them
+pub const MAX_RESULTS: usize = 32;
+pub const MAX_INPUT_SIZE: usize = 1024;
+
+// Seconds past the deadline a request with enough predictions is left to aggregate before it may expire anyway,
+// so a request aggregate cannot settle (no live or reputable submitters, a payout it cannot make) still refunds
+pub const AGGREGATION_GRACE_PERIOD: i64 = 24 * 60 * 60;
+
 #[account]
 pub struct InferenceRequest {          
-    pub request_id: u16,
+    pub request_id: u64,
     pub user: Pubkey,
     pub task_collection: Pubkey,      // associated task
     pub input_data: Vec<u8>,         // input data
     pub posted_at: i64,             // Timestamp of submission
+    pub deadline: i64,              // No predictions accepted after this timestamp, expirable once passed
+    pub commit_deadline: Option<i64>, // Set for commit-reveal tasks, commits close and reveals open after this timestamp
     pub required_predictions: u16,       // Number of predictions required
     pub status: RequestStatus,         // Status of the request
     pub results: Vec<ResultEntry>,     // Results submitted by nodes
+    pub result: Option<Vec<u8>>,       // Aggregated prediction, set once aggregated
+}
+
+impl InferenceRequest {
+    // space = disc (8) + request_id (8) + user (32) + task_collection (32) + input_data (4 + input_size) + posted_at (8)
+    // + deadline (8) + commit_deadline (1 + 8) + required_predictions (2) + status (1)
+    // + results (4 + result_count * (32 + 32 + 4 + prediction_size + 1 + 32)) + result (1 + 4 + prediction_size)
+    pub fn space(input_size: usize, result_count: usize, prediction_size: usize) -> usize {
+        8 + 8 + 32 + 32 + (4 + input_size) + 8 + 8 + 9 + 2 + 1
+            + (4 + result_count * (32 + 32 + 4 + prediction_size + 33))
+            + (1 + 4 + prediction_size)
+    }
+
+    pub fn is_commit_phase(&self, now: i64) -> bool {
+        self.commit_deadline.is_some_and(|commit_deadline| now <= commit_deadline)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now > self.deadline
+    }
+
+    // Reveals are accepted up to the deadline, after which commits left unrevealed no longer hold back aggregation
+    pub fn is_reveal_period_over(&self, now: i64) -> bool {
+        self.commit_deadline.is_none() || self.is_expired(now)
+    }
+
+    // Whether enough predictions are in, or revealed for commit-reveal requests, to aggregate the request
+    pub fn has_enough_predictions(&self) -> bool {
+        self.results.iter().filter(|result| result.is_revealed()).count() >= self.required_predictions as usize
+    }
+
+    // Past the deadline a request without enough predictions may expire right away, one with enough
+    // predictions only once the grace period has given aggregate its chance
+    pub fn can_expire(&self, now: i64) -> bool {
+        self.is_expired(now)
+            && (!self.has_enough_predictions() || now > self.deadline.saturating_add(AGGREGATION_GRACE_PERIOD))
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
@@ -72,18 +259,112 @@ pub enum RequestStatus {
     Pending,
     Aggregated,
     Completed,
+    Expired,
+}
+
+impl RequestStatus {
+    // No further predictions, aggregation or payouts can happen once a request is final
+    pub fn is_final(&self) -> bool {
+        matches!(self, RequestStatus::Aggregated | RequestStatus::Completed | RequestStatus::Expired)
+    }
+}
+
+// Holds the fee deposited with a request until it is paid out at aggregation
+#[account]
+pub struct FeeEscrow {
+    pub request: Pubkey,  // associated inference request
+    pub user: Pubkey,     // requester, receives the remainder and the rent
+    pub mint: Option<Pubkey>, // fee mint, held in the escrow's associated token account
+    pub amount: u64,      // fee in lamports excluding rent, or in base units of the mint
+    pub protocol_fee: u64, // part of the amount owed to the treasury, fixed when the request is posted
+}
+
+impl FeeEscrow {
+    // space = disc (8) + request (32) + user (32) + mint (1 + 32) + amount (8) + protocol_fee (8)
+    pub const SPACE: usize = 8 + 32 + 32 + 33 + 8 + 8;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct ResultEntry {
     pub weights_hash: [u8; 32],    // model identifier
-    pub prediction: Vec<u8>,    // Hash of the result
+    pub submitter: Pubkey,         // receives this result's share of the request fee
+    pub prediction: Vec<u8>,    // Hash of the result, empty until revealed when committed
+    pub commitment: Option<[u8; 32]>, // commit_pred's hash of the prediction, cleared once reveal_pred discloses it
+}
+
+impl ResultEntry {
+    pub fn is_revealed(&self) -> bool {
+        self.commitment.is_none()
+    }
+
+    // sha256(prediction || salt || weights_hash), binding the commitment to the model so another one cannot copy it and its reveal
+    pub fn commitment_hash(prediction: &[u8], salt: &[u8; 32], weights_hash: &[u8; 32]) -> [u8; 32] {
+        hashv(&[prediction, salt, weights_hash]).to_bytes()
+    }
+}
+
+// Models and results keyed by a one byte id, shared by the aggregation and reputation tests
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    pub fn model(id: u8, reputation: u8) -> ModelData {
+        ModelData {
+            task_collection: Pubkey::default(),
+            weights_hash: [id; 32],
+            tree_address: Pubkey::new_unique(),
+            leaf_index: id as u16,
+            reputation,
+            owner: Pubkey::new_unique(),
+            identity: ModelIdentity::Ed25519,
+            operators: vec![],
+            operator_share_bps: 0,
+        }
+    }
+
+    pub fn encoded_result(id: u8, prediction: Vec<u8>) -> ResultEntry {
+        ResultEntry {
+            weights_hash: [id; 32],
+            submitter: Pubkey::default(),
+            prediction,
+            commitment: None,
+        }
+    }
+
+    pub fn result(id: u8, prediction: f32) -> ResultEntry {
+        encoded_result(id, prediction.to_be_bytes().to_vec())
+    }
+
+    pub fn committed_result(id: u8) -> ResultEntry {
+        ResultEntry { commitment: Some([0; 32]), ..encoded_result(id, vec![]) }
+    }
 }
 
 // Unit tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::prediction::PredictionEncoding;
+    use crate::reputation::LossFunction;
+    use crate::schema::{FieldType, OutputRange, OutputSchema, MAX_INPUT_FIELDS};
+
+    fn settings(algorithm: AggregationAlgorithm) -> TaskSettings {
+        TaskSettings {
+            algorithm,
+            reputation_policy: ReputationPolicy {
+                loss: LossFunction::RelativeError,
+                tolerance: u64::MAX,
+                penalty_threshold: u64::MAX,
+                reward: 1,
+                penalty: 1,
+            },
+            min_predictions: 1,
+            min_reputation: 0,
+            min_fee: u64::MAX,
+            request_ttl: 60,
+            commit_window: Some(30),
+        }
+    }
 
     #[test]
     fn test_get_tree() {
@@ -100,8 +381,10 @@ mod tests {
         };
 
         let program_state = ProgramState {
-            creator: Pubkey::new_unique(),
+            admin: Pubkey::new_unique(),
+            pending_admin: None,
             tree_count: 2,
+            request_count: 0,
             trees: vec![tree_1.clone(), tree_2.clone()]
         };
 
@@ -130,8 +413,10 @@ mod tests {
         };
 
         let mut program_state = ProgramState {
-            creator: Pubkey::new_unique(),
+            admin: Pubkey::new_unique(),
+            pending_admin: None,
             tree_count: 2,
+            request_count: 0,
             trees: vec![tree_1.clone(), tree_2.clone()]
         };
 
@@ -150,4 +435,299 @@ mod tests {
         let result = program_state.increment_index(non_existent_tree);
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_inference_request_space() {
+        for prediction_size in [4, 8, 8 * 64] {
+            let request = InferenceRequest {
+                request_id: u64::MAX,
+                user: Pubkey::new_unique(),
+                task_collection: Pubkey::new_unique(),
+                input_data: vec![0; MAX_INPUT_SIZE],
+                posted_at: 0,
+                deadline: i64::MAX,
+                commit_deadline: Some(i64::MAX),
+                required_predictions: 0,
+                status: RequestStatus::Aggregated,
+                results: vec![ResultEntry {
+                    weights_hash: [0; 32],
+                    submitter: Pubkey::new_unique(),
+                    prediction: vec![0; prediction_size],
+                    commitment: Some([0; 32])
+                }; MAX_RESULTS],
+                result: Some(vec![0; prediction_size]),
+            };
+
+            // Discriminator plus the Borsh encoding of a full request
+            assert_eq!(8 + request.try_to_vec().unwrap().len(), InferenceRequest::space(MAX_INPUT_SIZE, MAX_RESULTS, prediction_size));
+        }
+    }
+
+    #[test]
+    fn test_program_state_space() {
+        for tree_count in [0, 1, MAX_TREES] {
+            let program_state = ProgramState {
+                admin: Pubkey::new_unique(),
+                pending_admin: Some(Pubkey::new_unique()),
+                tree_count: tree_count as u16,
+                request_count: u64::MAX,
+                trees: vec![TreeInfo {
+                    tree_address: Pubkey::new_unique(),
+                    tree_config: Pubkey::new_unique(),
+                    current_index: u16::MAX,
+                }; tree_count],
+            };
+
+            assert_eq!(8 + program_state.try_to_vec().unwrap().len(), ProgramState::space(tree_count));
+        }
+    }
+
+    #[test]
+    fn test_task_data_space() {
+        let task = TaskData {
+            collection_mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            schema: TaskSchema {
+                inputs: vec![FieldType::U8; MAX_INPUT_FIELDS],
+                output: OutputSchema {
+                    kind: OutputKind::Vector { len: 4 },
+                    encoding: PredictionEncoding::FixedPoint { scale: 6 },
+                    range: Some(OutputRange::FixedPoint { lower: i64::MIN, upper: i64::MAX }),
+                },
+            },
+            settings: settings(AggregationAlgorithm::TrimmedMean { trim_bps: 4_999 }),
+            fee_mint: Some(Pubkey::new_unique()),
+            model_count: u16::MAX,
+            paused: true,
+        };
+
+        assert_eq!(8 + task.try_to_vec().unwrap().len(), TaskData::SPACE);
+    }
+
+    #[test]
+    fn test_task_settings_validate() {
+        let scalar = OutputKind::Scalar;
+        assert!(settings(AggregationAlgorithm::WeightedMedian).validate(&scalar).is_ok());
+        assert!(settings(AggregationAlgorithm::GeometricMedian).validate(&OutputKind::Vector { len: 4 }).is_ok());
+
+        let mut loose = settings(AggregationAlgorithm::WeightedMean);
+        loose.reputation_policy.tolerance = 10;
+        loose.reputation_policy.penalty_threshold = 5;
+        let invalid = [
+            (settings(AggregationAlgorithm::GeometricMedian), scalar),
+            (settings(AggregationAlgorithm::TrimmedMean { trim_bps: 5_000 }), scalar),
+            (TaskSettings { min_predictions: 0, ..settings(AggregationAlgorithm::WeightedMean) }, scalar),
+            (TaskSettings { request_ttl: 0, ..settings(AggregationAlgorithm::WeightedMean) }, scalar),
+            (TaskSettings { commit_window: Some(0), ..settings(AggregationAlgorithm::WeightedMean) }, scalar),
+            (TaskSettings { commit_window: Some(60), ..settings(AggregationAlgorithm::WeightedMean) }, scalar),
+            (loose, scalar),
+        ];
+        for (settings, output) in invalid {
+            assert!(settings.validate(&output).is_err());
+        }
+    }
+
+    #[test]
+    fn test_model_data_space() {
+        let model = ModelData {
+            task_collection: Pubkey::new_unique(),
+            weights_hash: [7; 32],
+            tree_address: Pubkey::new_unique(),
+            leaf_index: u16::MAX,
+            reputation: u8::MAX,
+            owner: Pubkey::new_unique(),
+            identity: ModelIdentity::Secp256k1 { eth_address: [9; 20] },
+            operators: vec![Pubkey::new_unique(); MAX_OPERATORS],
+            operator_share_bps: 10_000,
+        };
+
+        assert_eq!(8 + model.try_to_vec().unwrap().len(), ModelData::space(MAX_OPERATORS));
+    }
+
+    #[test]
+    fn test_model_operators() {
+        let owner = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let mut model = ModelData {
+            task_collection: Pubkey::new_unique(),
+            weights_hash: [7; 32],
+            tree_address: Pubkey::new_unique(),
+            leaf_index: 0,
+            reputation: 1,
+            owner,
+            identity: ModelIdentity::Ed25519,
+            operators: vec![],
+            operator_share_bps: 0,
+        };
+        assert!(model.can_submit(&owner));
+        assert!(!model.can_submit(&operator));
+
+        model.add_operator(operator).unwrap();
+        // Adding an operator twice is a no-op
+        model.add_operator(operator).unwrap();
+        assert_eq!(model.operators, vec![operator]);
+        assert!(model.can_submit(&operator));
+        assert!(model.can_submit(&owner));
+
+        model.add_operator(Pubkey::new_unique()).unwrap();
+        assert!(model.add_operator(Pubkey::new_unique()).is_err());
+
+        model.remove_operator(&operator).unwrap();
+        assert!(!model.can_submit(&operator));
+        assert!(model.remove_operator(&operator).is_err());
+    }
+
+    #[test]
+    fn test_commitment_hash() {
+        let salt = [3; 32];
+        let commitment = ResultEntry::commitment_hash(&[1, 2, 3, 4], &salt, &[7; 32]);
+        assert_eq!(commitment, ResultEntry::commitment_hash(&[1, 2, 3, 4], &salt, &[7; 32]));
+
+        // Any change to the prediction, the salt or the model gives another commitment
+        assert_ne!(commitment, ResultEntry::commitment_hash(&[1, 2, 3, 5], &salt, &[7; 32]));
+        assert_ne!(commitment, ResultEntry::commitment_hash(&[1, 2, 3, 4], &[4; 32], &[7; 32]));
+        assert_ne!(commitment, ResultEntry::commitment_hash(&[1, 2, 3, 4], &salt, &[8; 32]));
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let request = InferenceRequest {
+            request_id: 0,
+            user: Pubkey::new_unique(),
+            task_collection: Pubkey::new_unique(),
+            input_data: vec![],
+            posted_at: 100,
+            deadline: 200,
+            commit_deadline: None,
+            required_predictions: 1,
+            status: RequestStatus::Pending,
+            results: vec![],
+            result: None,
+        };
+
+        // Predictions are still accepted at the deadline itself
+        assert!(!request.is_expired(100));
+        assert!(!request.is_expired(200));
+        assert!(request.is_expired(201));
+        assert!(!request.is_commit_phase(100));
+
+        // Commits are accepted up to and including the commit deadline
+        let request = InferenceRequest { commit_deadline: Some(150), ..request };
+        assert!(request.is_commit_phase(150));
+        assert!(!request.is_commit_phase(151));
+
+        // The reveal period runs from the commit deadline to the request deadline
+        assert!(!request.is_reveal_period_over(151));
+        assert!(!request.is_reveal_period_over(200));
+        assert!(request.is_reveal_period_over(201));
+    }
+
+    #[test]
+    fn test_has_enough_predictions() {
+        let request = InferenceRequest {
+            request_id: 0,
+            user: Pubkey::new_unique(),
+            task_collection: Pubkey::new_unique(),
+            input_data: vec![],
+            posted_at: 100,
+            deadline: 200,
+            commit_deadline: Some(150),
+            required_predictions: 2,
+            status: RequestStatus::Pending,
+            results: vec![fixtures::result(1, 1.0), fixtures::committed_result(2)],
+            result: None,
+        };
+        // Unrevealed commits do not count
+        assert!(!request.has_enough_predictions());
+
+        let request = InferenceRequest { results: vec![fixtures::result(1, 1.0), fixtures::result(2, 2.0)], ..request };
+        assert!(request.has_enough_predictions());
+    }
+
+    #[test]
+    fn test_can_expire() {
+        let request = InferenceRequest {
+            request_id: 0,
+            user: Pubkey::new_unique(),
+            task_collection: Pubkey::new_unique(),
+            input_data: vec![],
+            posted_at: 100,
+            deadline: 200,
+            commit_deadline: None,
+            required_predictions: 2,
+            status: RequestStatus::Pending,
+            results: vec![fixtures::result(1, 1.0)],
+            result: None,
+        };
+        // Without enough predictions the request expires as soon as the deadline has passed
+        assert!(!request.can_expire(200));
+        assert!(request.can_expire(201));
+
+        // With enough predictions it is left to aggregate for the grace period
+        let request = InferenceRequest { results: vec![fixtures::result(1, 1.0), fixtures::result(2, 2.0)], ..request };
+        assert!(!request.can_expire(201));
+        assert!(!request.can_expire(200 + AGGREGATION_GRACE_PERIOD));
+        assert!(request.can_expire(201 + AGGREGATION_GRACE_PERIOD));
+
+        // The grace period cannot overflow the deadline
+        let request = InferenceRequest { deadline: i64::MAX - 1, ..request };
+        assert!(!request.can_expire(i64::MAX));
+    }
+
+    #[test]
+    fn test_protocol_config_space() {
+        let config = ProtocolConfig::with_defaults(Pubkey::new_unique());
+        assert_eq!(8 + config.try_to_vec().unwrap().len(), ProtocolConfig::SPACE);
+    }
+
+    #[test]
+    fn test_protocol_config_validate() {
+        let treasury = Pubkey::new_unique();
+        assert!(ProtocolConfig::with_defaults(treasury).validate().is_ok());
+        assert!(ProtocolConfig { max_results: MAX_RESULTS as u16, max_input_size: MAX_INPUT_SIZE as u16, ..ProtocolConfig::with_defaults(treasury) }.validate().is_ok());
+
+        let invalid = [
+            ProtocolConfig { initial_reputation: 0, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { max_results: 0, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { max_results: MAX_RESULTS as u16 + 1, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { max_input_size: 0, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { max_input_size: MAX_INPUT_SIZE as u16 + 1, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { min_model_count: 0, ..ProtocolConfig::with_defaults(treasury) },
+            ProtocolConfig { protocol_fee_bps: MAX_PROTOCOL_FEE_BPS + 1, ..ProtocolConfig::with_defaults(treasury) },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_protocol_fee() {
+        let config = ProtocolConfig { protocol_fee_bps: 250, ..ProtocolConfig::with_defaults(Pubkey::new_unique()) };
+        assert_eq!(config.protocol_fee(1_000), 25);
+        // Rounded down, the dust stays with the fee split between models
+        assert_eq!(config.protocol_fee(39), 0);
+        assert_eq!(config.protocol_fee(u64::MAX), u64::MAX / 40);
+        assert_eq!(ProtocolConfig::with_defaults(Pubkey::new_unique()).protocol_fee(1_000), 0);
+    }
+
+    #[test]
+    fn test_request_status_is_final() {
+        assert!(!RequestStatus::Pending.is_final());
+        assert!(RequestStatus::Aggregated.is_final());
+        assert!(RequestStatus::Completed.is_final());
+        assert!(RequestStatus::Expired.is_final());
+    }
+
+    #[test]
+    fn test_fee_escrow_space() {
+        let escrow = FeeEscrow {
+            request: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            mint: Some(Pubkey::new_unique()),
+            amount: u64::MAX,
+            protocol_fee: u64::MAX,
+        };
+
+        assert_eq!(8 + escrow.try_to_vec().unwrap().len(), FeeEscrow::SPACE);
+    }
+}
@@ -1,58 +1,202 @@
 use anchor_lang::prelude::*;
 use crate::error::Errors;
-use solana_program::sysvar::instructions::{load_instruction_at_checked, load_current_index_checked};
+use solana_program::{ed25519_program, secp256k1_program};
+use solana_program::sysvar::instructions::load_instruction_at_checked;
 
+// Prefix of the message a model owner signs to register weights, keeps the signature from meaning anything else
+pub const WEIGHTS_DOMAIN: &[u8] = b"deinference:register_weights:v1";
+
+// Message signed by the model owner: domain || program id || task collection || model owner || weights hash.
+// The owner is part of it so an Ethereum signature, made by a key other than the owner's, registers the model to that owner only
+pub fn weights_message(program_id: &Pubkey, task_collection: &Pubkey, model_owner: &Pubkey, weights_hash: &[u8; 32]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(WEIGHTS_DOMAIN.len() + 32 + 32 + 32 + 32);
+    message.extend_from_slice(WEIGHTS_DOMAIN);
+    message.extend_from_slice(program_id.as_ref());
+    message.extend_from_slice(task_collection.as_ref());
+    message.extend_from_slice(model_owner.as_ref());
+    message.extend_from_slice(weights_hash);
+    message
+}
+
+// Prefix of the message an Ethereum identity signs to vouch for a prediction relayed on its behalf
+pub const PREDICTION_DOMAIN: &[u8] = b"deinference:submit_prediction:v1";
+
+// Message attesting a prediction: domain || program id || request id (le) || weights hash || prediction
+pub fn prediction_message(program_id: &Pubkey, request_id: u64, weights_hash: &[u8; 32], prediction: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(PREDICTION_DOMAIN.len() + 32 + 8 + 32 + prediction.len());
+    message.extend_from_slice(PREDICTION_DOMAIN);
+    message.extend_from_slice(program_id.as_ref());
+    message.extend_from_slice(&request_id.to_le_bytes());
+    message.extend_from_slice(weights_hash);
+    message.extend_from_slice(prediction);
+    message
+}
+
+// EIP-191 personal message, what Ethereum wallets sign for `personal_sign`.
+// The Secp256k1 precompile hashes the whole message, so the prefix is part of what it verifies
+pub fn eth_signed_message(message: &[u8]) -> Vec<u8> {
+    let mut signed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    signed.extend_from_slice(message);
+    signed
+}
+
+// Succeeds if any Ed25519 precompile instruction in the transaction verified `signature` by `expected_public_key` over `message`.
+// The precompile has already checked every signature it carries by the time this runs, so finding the entry is enough
 pub fn verify_ed25519_instruction(
     instruction_sysvar: &AccountInfo,
     expected_public_key: &[u8],
     message: &[u8],
     signature: &[u8]
 ) -> Result<()> {
-    let current_index = load_current_index_checked(instruction_sysvar)?;
-    if current_index == 0 {
-        return Err(Errors::MissingEd25519Instruction.into());
+    let found = find_precompile_entry(instruction_sysvar, &ed25519_program::ID, |data, index| {
+        ed25519_instruction_contains(data, index, expected_public_key, message, signature)
+    })?;
+    match found {
+        Some(true) => Ok(()),
+        Some(false) => Err(Errors::InvalidSignature.into()),
+        None => Err(Errors::MissingEd25519Instruction.into()),
     }
+}
 
-    let ed25519_instruction = load_instruction_at_checked((current_index - 1) as usize, instruction_sysvar)?;
-    
-    // Verify the content of the Ed25519 instruction
-    let instruction_data = ed25519_instruction.data;
-    if instruction_data.len() < 2 {
-        return Err(Errors::InvalidEd25519Instruction.into());
+// Succeeds if any Secp256k1 precompile instruction in the transaction verified a signature by `eth_address` over `message`
+pub fn verify_secp256k1_instruction(
+    instruction_sysvar: &AccountInfo,
+    eth_address: &[u8; 20],
+    message: &[u8]
+) -> Result<()> {
+    let found = find_precompile_entry(instruction_sysvar, &secp256k1_program::ID, |data, index| {
+        secp256k1_instruction_contains(data, index, eth_address, message)
+    })?;
+    match found {
+        Some(true) => Ok(()),
+        Some(false) => Err(Errors::InvalidSignature.into()),
+        None => Err(Errors::MissingSecp256k1Instruction.into()),
     }
+}
+
+// Runs `contains` over the data of every instruction to `precompile` in the transaction.
+// None if there is no such instruction, otherwise whether any of them contains the entry
+fn find_precompile_entry(
+    instruction_sysvar: &AccountInfo,
+    precompile: &Pubkey,
+    contains: impl Fn(&[u8], u16) -> Result<bool>
+) -> Result<Option<bool>> {
+    let instruction_count = {
+        let data = instruction_sysvar.try_borrow_data()?;
+        let count = data.get(0..2).ok_or(ProgramError::InvalidAccountData)?;
+        u16::from_le_bytes([count[0], count[1]])
+    };
 
-    let num_signatures = instruction_data[0];
-    if num_signatures != 1 {
-        return Err(Errors::InvalidEd25519Instruction.into());
+    let mut found = None;
+    for index in 0..instruction_count {
+        let instruction = load_instruction_at_checked(index.into(), instruction_sysvar)?;
+        if &instruction.program_id != precompile {
+            continue;
+        }
+        if contains(&instruction.data, index)? {
+            return Ok(Some(true));
+        }
+        found = Some(false);
     }
 
-    // Parse Ed25519SignatureOffsets
-    let offsets: Ed25519SignatureOffsets = Ed25519SignatureOffsets::try_from_slice(&instruction_data[2..16])?;
+    Ok(found)
+}
 
-    // Verify public key
-    let pubkey_start = offsets.public_key_offset as usize;
-    let pubkey_end = pubkey_start + 32;
-    if &instruction_data[pubkey_start..pubkey_end] != expected_public_key {
-        return Err(Errors::InvalidPublicKey.into());
-    }
+// Layout: num_signatures (1) + padding (1) + num_signatures * offsets (14), followed by the data the offsets point into
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_SIZE: usize = 14;
 
-    // Verify message
-    let msg_start = offsets.message_data_offset as usize;
-    let msg_end = msg_start + offsets.message_data_size as usize;
-    if &instruction_data[msg_start..msg_end] != message {
-        return Err(Errors::InvalidMessage.into());
+// Whether the data of the Ed25519 instruction at `instruction_index` carries the expected entry.
+// Only entries reading all their data from that same instruction are considered, an entry pointing at
+// another instruction could be made to verify bytes that differ from the ones compared here
+fn ed25519_instruction_contains(
+    data: &[u8],
+    instruction_index: u16,
+    expected_public_key: &[u8],
+    message: &[u8],
+    signature: &[u8]
+) -> Result<bool> {
+    let num_signatures = *data.first().ok_or(error!(Errors::InvalidEd25519Instruction))? as usize;
+    let is_self = |index: u16| index == u16::MAX || index == instruction_index;
+
+    for i in 0..num_signatures {
+        let start = SIGNATURE_OFFSETS_START + i * SIGNATURE_OFFSETS_SIZE;
+        let offsets_data = data.get(start..start + SIGNATURE_OFFSETS_SIZE).ok_or(error!(Errors::InvalidEd25519Instruction))?;
+        let offsets = Ed25519SignatureOffsets::try_from_slice(offsets_data)?;
+
+        if !is_self(offsets.signature_instruction_index)
+            || !is_self(offsets.public_key_instruction_index)
+            || !is_self(offsets.message_instruction_index)
+        {
+            continue;
+        }
+
+        let entry_public_key = slice(data, offsets.public_key_offset, 32)?;
+        let entry_message = slice(data, offsets.message_data_offset, offsets.message_data_size.into())?;
+        let entry_signature = slice(data, offsets.signature_offset, 64)?;
+        if entry_public_key == expected_public_key && entry_message == message && entry_signature == signature {
+            return Ok(true);
+        }
     }
 
-    // Verify signature
-    let sig_start = offsets.signature_offset as usize;
-    let sig_end = sig_start + 64;
-    if &instruction_data[sig_start..sig_end] != signature {
-        return Err(Errors::InvalidSignature.into());
+    Ok(false)
+}
+
+fn slice(data: &[u8], offset: u16, len: usize) -> Result<&[u8]> {
+    let start = usize::from(offset);
+    data.get(start..start + len).ok_or(error!(Errors::InvalidEd25519Instruction))
+}
+
+// Layout: num_signatures (1) + num_signatures * offsets (11), followed by the data the offsets point into
+const SECP256K1_OFFSETS_START: usize = 1;
+const SECP256K1_OFFSETS_SIZE: usize = 11;
+
+// Whether the data of the Secp256k1 instruction at `instruction_index` carries an entry for `eth_address` over `message`.
+// The precompile has no "this instruction" sentinel, so every index must be the instruction's own
+fn secp256k1_instruction_contains(
+    data: &[u8],
+    instruction_index: u16,
+    eth_address: &[u8; 20],
+    message: &[u8]
+) -> Result<bool> {
+    let num_signatures = *data.first().ok_or(error!(Errors::InvalidSecp256k1Instruction))? as usize;
+    let is_self = |index: u8| u16::from(index) == instruction_index;
+
+    for i in 0..num_signatures {
+        let start = SECP256K1_OFFSETS_START + i * SECP256K1_OFFSETS_SIZE;
+        let offsets_data = data.get(start..start + SECP256K1_OFFSETS_SIZE).ok_or(error!(Errors::InvalidSecp256k1Instruction))?;
+        let offsets = Secp256k1SignatureOffsets::try_from_slice(offsets_data)?;
+
+        if !is_self(offsets.signature_instruction_index)
+            || !is_self(offsets.eth_address_instruction_index)
+            || !is_self(offsets.message_instruction_index)
+        {
+            continue;
+        }
+
+        let entry_address = slice(data, offsets.eth_address_offset, 20).map_err(|_| error!(Errors::InvalidSecp256k1Instruction))?;
+        let entry_message = slice(data, offsets.message_data_offset, offsets.message_data_size.into())
+            .map_err(|_| error!(Errors::InvalidSecp256k1Instruction))?;
+        // 64 byte signature followed by the recovery id
+        slice(data, offsets.signature_offset, 65).map_err(|_| error!(Errors::InvalidSecp256k1Instruction))?;
+        if entry_address == eth_address && entry_message == message {
+            return Ok(true);
+        }
     }
 
-    Ok(())
+    Ok(false)
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize)]
+struct Secp256k1SignatureOffsets {
+    signature_offset: u16,
+    signature_instruction_index: u8,
+    eth_address_offset: u16,
+    eth_address_instruction_index: u8,
+    message_data_offset: u16,
+    message_data_size: u16,
+    message_instruction_index: u8,
+}
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 struct Ed25519SignatureOffsets {
@@ -63,4 +207,154 @@ struct Ed25519SignatureOffsets {
     message_data_offset: u16,
     message_data_size: u16,
     message_instruction_index: u16,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_message() {
+        let program_id = Pubkey::new_unique();
+        let task = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let message = weights_message(&program_id, &task, &owner, &[7; 32]);
+
+        assert_eq!(message.len(), WEIGHTS_DOMAIN.len() + 128);
+        assert!(message.starts_with(WEIGHTS_DOMAIN));
+        assert_eq!(&message[WEIGHTS_DOMAIN.len()..WEIGHTS_DOMAIN.len() + 32], program_id.as_ref());
+        assert_eq!(&message[WEIGHTS_DOMAIN.len() + 32..WEIGHTS_DOMAIN.len() + 64], task.as_ref());
+        assert_eq!(&message[WEIGHTS_DOMAIN.len() + 64..WEIGHTS_DOMAIN.len() + 96], owner.as_ref());
+        assert_eq!(&message[WEIGHTS_DOMAIN.len() + 96..], &[7; 32]);
+
+        // The same hash signed for another task or owner gives a different message
+        assert_ne!(message, weights_message(&program_id, &Pubkey::new_unique(), &owner, &[7; 32]));
+        assert_ne!(message, weights_message(&program_id, &task, &Pubkey::new_unique(), &[7; 32]));
+    }
+
+    // Builds Ed25519 instruction data carrying `entries` of (public key, message, signature), each read from `instruction_index`
+    fn ed25519_data(entries: &[([u8; 32], Vec<u8>, [u8; 64])], instruction_index: u16) -> Vec<u8> {
+        let mut data = vec![entries.len() as u8, 0];
+        let mut payload = vec![];
+        let payload_start = SIGNATURE_OFFSETS_START + entries.len() * SIGNATURE_OFFSETS_SIZE;
+        for (public_key, message, signature) in entries {
+            let public_key_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(public_key);
+            let signature_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(signature);
+            let message_data_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(message);
+            let offsets = Ed25519SignatureOffsets {
+                signature_offset,
+                signature_instruction_index: instruction_index,
+                public_key_offset,
+                public_key_instruction_index: instruction_index,
+                message_data_offset,
+                message_data_size: message.len() as u16,
+                message_instruction_index: instruction_index,
+            };
+            data.extend_from_slice(&offsets.try_to_vec().unwrap());
+        }
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    #[test]
+    fn test_ed25519_instruction_contains() {
+        let first = ([1; 32], b"first".to_vec(), [2; 64]);
+        let second = ([3; 32], b"second".to_vec(), [4; 64]);
+        let data = ed25519_data(&[first.clone(), second.clone()], u16::MAX);
+
+        // Any entry of the instruction can match, all three fields must agree
+        assert!(ed25519_instruction_contains(&data, 0, &first.0, &first.1, &first.2).unwrap());
+        assert!(ed25519_instruction_contains(&data, 0, &second.0, &second.1, &second.2).unwrap());
+        assert!(!ed25519_instruction_contains(&data, 0, &first.0, &second.1, &first.2).unwrap());
+        assert!(!ed25519_instruction_contains(&data, 0, &first.0, &first.1, &second.2).unwrap());
+        assert!(!ed25519_instruction_contains(&data, 0, &second.0, &first.1, &first.2).unwrap());
+
+        // The instruction may refer to itself by its own index
+        let data = ed25519_data(std::slice::from_ref(&first), 3);
+        assert!(ed25519_instruction_contains(&data, 3, &first.0, &first.1, &first.2).unwrap());
+    }
+
+    #[test]
+    fn test_ed25519_instruction_ignores_other_instructions() {
+        let entry = ([1; 32], b"message".to_vec(), [2; 64]);
+        let data = ed25519_data(std::slice::from_ref(&entry), 1);
+        assert!(!ed25519_instruction_contains(&data, 0, &entry.0, &entry.1, &entry.2).unwrap());
+    }
+
+    // Builds Secp256k1 instruction data carrying `entries` of (eth address, message), each read from `instruction_index`
+    fn secp256k1_data(entries: &[([u8; 20], Vec<u8>)], instruction_index: u8) -> Vec<u8> {
+        let mut data = vec![entries.len() as u8];
+        let mut payload = vec![];
+        let payload_start = SECP256K1_OFFSETS_START + entries.len() * SECP256K1_OFFSETS_SIZE;
+        for (eth_address, message) in entries {
+            let eth_address_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(eth_address);
+            let signature_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(&[5; 65]);
+            let message_data_offset = (payload_start + payload.len()) as u16;
+            payload.extend_from_slice(message);
+            let offsets = Secp256k1SignatureOffsets {
+                signature_offset,
+                signature_instruction_index: instruction_index,
+                eth_address_offset,
+                eth_address_instruction_index: instruction_index,
+                message_data_offset,
+                message_data_size: message.len() as u16,
+                message_instruction_index: instruction_index,
+            };
+            data.extend_from_slice(&offsets.try_to_vec().unwrap());
+        }
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    #[test]
+    fn test_secp256k1_instruction_contains() {
+        let first = ([1; 20], b"first".to_vec());
+        let second = ([2; 20], b"second".to_vec());
+        let data = secp256k1_data(&[first.clone(), second.clone()], 1);
+
+        assert!(secp256k1_instruction_contains(&data, 1, &first.0, &first.1).unwrap());
+        assert!(secp256k1_instruction_contains(&data, 1, &second.0, &second.1).unwrap());
+        assert!(!secp256k1_instruction_contains(&data, 1, &first.0, &second.1).unwrap());
+
+        // Entries must read from the instruction itself
+        assert!(!secp256k1_instruction_contains(&data, 0, &first.0, &first.1).unwrap());
+
+        // Offsets past the end of the data are rejected instead of panicking
+        assert!(secp256k1_instruction_contains(&data[..data.len() - 1], 1, &second.0, &second.1).is_err());
+        assert!(secp256k1_instruction_contains(&[], 1, &first.0, &first.1).is_err());
+    }
+
+    #[test]
+    fn test_eth_signed_message() {
+        assert_eq!(eth_signed_message(b"hello"), b"\x19Ethereum Signed Message:\n5hello".to_vec());
+        let message = prediction_message(&Pubkey::new_unique(), 7, &[1; 32], &[2; 4]);
+        assert!(eth_signed_message(&message).ends_with(&message));
+        assert!(eth_signed_message(&message).starts_with(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes()));
+    }
+
+    #[test]
+    fn test_ed25519_instruction_out_of_bounds() {
+        let entry = ([1; 32], b"message".to_vec(), [2; 64]);
+        let data = ed25519_data(std::slice::from_ref(&entry), u16::MAX);
+
+        // Offsets pointing past the end of the data are rejected instead of panicking
+        let truncated = &data[..data.len() - 1];
+        assert!(ed25519_instruction_contains(truncated, 0, &entry.0, &entry.1, &entry.2).is_err());
+
+        // So are signature counts with no room for their offsets
+        let mut overcounted = data.clone();
+        overcounted[0] = 200;
+        assert!(ed25519_instruction_contains(&overcounted, 0, &[9; 32], &entry.1, &entry.2).is_err());
+
+        let mut bad_offset = data;
+        bad_offset[SIGNATURE_OFFSETS_START..SIGNATURE_OFFSETS_START + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(ed25519_instruction_contains(&bad_offset, 0, &entry.0, &entry.1, &entry.2).is_err());
+
+        assert!(ed25519_instruction_contains(&[], 0, &entry.0, &entry.1, &entry.2).is_err());
+    }
+}
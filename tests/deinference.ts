@@ -11,7 +11,7 @@ import { percentAmount, PublicKey as UmiPK, generateSigner, signerIdentity, crea
 import { createUmi } from '@metaplex-foundation/umi-bundle-defaults';
 import { dasApi } from '@metaplex-foundation/digital-asset-standard-api';
 import { PublicKey, Keypair, TransactionConfirmationStrategy, Transaction, sendAndConfirmTransaction, TransactionSignature, SYSVAR_INSTRUCTIONS_PUBKEY,
-  Ed25519Program, 
+  Ed25519Program, Secp256k1Program, TransactionInstruction, 
   LAMPORTS_PER_SOL} from "@solana/web3.js";
 import { assert } from "chai";
 import { ChangeLogEventV1, ConcurrentMerkleTreeAccount, createAllocTreeIx, deserializeChangeLogEventV1, ValidDepthSizePair } from "@solana/spl-account-compression";
@@ -21,6 +21,7 @@ import { base58 } from "@metaplex-foundation/umi/serializers";
 import * as borsh from "borsh";
 import { execSync } from "child_process";
 import nacl from 'tweetnacl';
+import { createHash, randomBytes } from "crypto";
 import { matchesGlob } from "path";
 
 describe("deinference", () => {
@@ -46,7 +47,7 @@ describe("deinference", () => {
   let collectionMetadataAccount: PublicKey;
   let treeAccount: ConcurrentMerkleTreeAccount;
   let collection_mint: KeypairSigner;
-  let request_id: number = 0;
+  let request_id: anchor.BN;
 
   // Configure the client to use the local cluster.
   const provider = anchor.AnchorProvider.env();
@@ -109,6 +110,75 @@ describe("deinference", () => {
       new PublicKey(MPL_BUBBLEGUM_PROGRAM_ID)
   );
 
+  // Derive the pda storing a model registered to a task
+  const modelPda = (collection: PublicKey, weightsHash: Buffer) => PublicKey.findProgramAddressSync(
+    [Buffer.from("model"), collection.toBuffer(), weightsHash],
+    program.programId
+  )[0];
+
+  // Message a model owner signs to register weights to a task, domain || program id || task || model owner || weights hash
+  const weightsMessage = (collection: PublicKey, owner: PublicKey, weightsHash: Buffer) => Buffer.concat([
+    Buffer.from("deinference:register_weights:v1"),
+    program.programId.toBuffer(),
+    collection.toBuffer(),
+    owner.toBuffer(),
+    weightsHash
+  ]);
+
+  // Message an Ethereum identity signs to vouch for a prediction, domain || program id || request id || weights hash || prediction
+  const predictionMessage = (requestId: anchor.BN, weightsHash: Buffer, prediction: Buffer) => Buffer.concat([
+    Buffer.from("deinference:submit_prediction:v1"),
+    program.programId.toBuffer(),
+    requestId.toArrayLike(Buffer, "le", 8),
+    weightsHash,
+    prediction
+  ]);
+
+  // EIP-191 personal message, as signed by Ethereum wallets
+  const ethSignedMessage = (message: Buffer) => Buffer.concat([
+    Buffer.from(`\x19Ethereum Signed Message:\n${message.length}`),
+    message
+  ]);
+
+  // Model owner holding only an Ethereum key, the cNFT goes to a Solana wallet of theirs
+  const ethModel = {
+    privateKey: randomBytes(32),
+    weightsHash: createHash("sha256").update("eth model weights").digest()
+  };
+
+  // One Ed25519 instruction verifying a signature by `publicKey` over each message, all entries share the public key
+  const ed25519BatchInstruction = (publicKey: PublicKey, messages: Buffer[], signatures: Uint8Array[]) => {
+    const offsetsSize = 14;
+    const publicKeyOffset = 2 + messages.length * offsetsSize;
+    const header = Buffer.alloc(publicKeyOffset);
+    header.writeUInt8(messages.length, 0);
+    const payload: Buffer[] = [publicKey.toBuffer()];
+    let offset = publicKeyOffset + 32;
+    messages.forEach((message, i) => {
+      const entry = 2 + i * offsetsSize;
+      header.writeUInt16LE(offset, entry); // signature offset
+      header.writeUInt16LE(0xffff, entry + 2); // signature instruction index, this instruction
+      header.writeUInt16LE(publicKeyOffset, entry + 4);
+      header.writeUInt16LE(0xffff, entry + 6);
+      header.writeUInt16LE(offset + 64, entry + 8); // message offset
+      header.writeUInt16LE(message.length, entry + 10);
+      header.writeUInt16LE(0xffff, entry + 12);
+      payload.push(Buffer.from(signatures[i]), message);
+      offset += 64 + message.length;
+    });
+    return new TransactionInstruction({
+      keys: [],
+      programId: Ed25519Program.programId,
+      data: Buffer.concat([header, ...payload])
+    });
+  };
+
+  // Derive the pda of a request from the id the program assigned it
+  const requestPda = (id: anchor.BN) => PublicKey.findProgramAddressSync(
+    [Buffer.from("request"), id.toArrayLike(Buffer, "le", 8)],
+    program.programId
+  )[0];
+
   // Derive bubblegum signer pda
   const [bubblegumSigner] = PublicKey.findProgramAddressSync(
     // `collection_cpi` is a custom prefix required by the Bubblegum program
@@ -123,11 +193,38 @@ describe("deinference", () => {
   };
   const canopyDepth = maxDepthSizePair.maxDepth - 5;
 
+  // Input schema matches the RequestData struct serialized in the request tests
+  const taskSchema = {
+    inputs: [{ u32: {} }, { u32: {} }, { string: {} }],
+    output: {
+      kind: { scalar: {} },
+      encoding: { float32: {} },
+      range: null
+    }
+  };
+  // Owner controlled task settings, requests accept predictions for an hour unless posted with their own deadline
+  const taskSettings = {
+    algorithm: { weightedMedian: {} },
+    reputationPolicy: {
+      loss: { absoluteError: {} },
+      tolerance: new anchor.BN(500_000), // millionths of a prediction unit
+      penaltyThreshold: new anchor.BN(1_500_000),
+      reward: 1,
+      penalty: 1
+    },
+    minPredictions: 1,
+    minReputation: 0,
+    minFee: new anchor.BN(0),
+    requestTtl: new anchor.BN(60 * 60),
+    commitWindow: null // predictions are submitted in the clear
+  };
+
   // Define metadata for NFT
   const metadata = {
-    uri: '12345678901234567890123456789012', // Must be 32 bytes
+    uri: 'https://example.com/models/test-nft.json',
     name: 'TEST-NFT',
-    symbol: 'TNFT'
+    symbol: 'TNFT',
+    weightsHash: createHash("sha256").update("test-nft weights").digest()
   }
 
   before(async () => {
@@ -137,43 +234,8 @@ describe("deinference", () => {
       stdio: 'inherit', // Pass output to the terminal
     });
 
-    // Close state accounts if they already exist
-    const programStateAccountInfo = await provider.connection.getAccountInfo(programStatePda);
-    if (programStateAccountInfo) {
-      const closeStateAccountTx = await program.methods.closeAccount()
-      .accounts({
-        pdaAccount: programStatePda,
-        receiver: wallet.payer.publicKey
-      }).signers([wallet.payer]).rpc({ commitment: 'confirmed'});
-    }
-
-    const taskDataStateAccountInfo = await provider.connection.getAccountInfo(taskDataPda);
-    if (taskDataStateAccountInfo) {
-      const closeTaskDataAccountTx = await program.methods.closeAccount()
-      .accounts({
-        pdaAccount: taskDataPda,
-        receiver: wallet.payer.publicKey
-      }).signers([wallet.payer]).rpc({ commitment: 'confirmed'});
-    } 
-
-    const request_id_buffer = Buffer.alloc(2);
-    request_id_buffer.writeUInt16LE(request_id);
-    [requestStatePda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("request"), request_id_buffer], // Same seed as in the Rust program
-      program.programId
-    );
+    // anchor test starts a fresh validator, so none of the program accounts exist yet
 
-    const requestStateAccountInfo = await provider.connection.getAccountInfo(requestStatePda);
-    if (requestStateAccountInfo) {
-      console.log("Attempting to close request state");
-      const closeRequestStateAccountTx = await program.methods.closeAccount()
-      .accounts({
-        pdaAccount: requestStatePda,
-        receiver: wallet.payer.publicKey
-      }).signers([wallet.payer]).rpc({ commitment: 'confirmed'});
-      console.log("Request state account closed:", closeRequestStateAccountTx);
-    } 
-    
     // Give tree account space
     const allocTreeIx = await createAllocTreeIx(
       connection,
@@ -209,14 +271,6 @@ describe("deinference", () => {
 
     await confirmTransaction(createNftSignature);
 
-    const updateNftUpdateAuthTx = await updateV1(umi, {
-      mint: collection_mint.publicKey,
-      newUpdateAuthority: tree_owner.toBase58() as UmiPK
-    }).sendAndConfirm(umi);
-
-    const updateNftUpdateAuthSignature = base58.deserialize(updateNftUpdateAuthTx.signature)[0];
-    console.log(`https://explorer.solana.com/tx/${updateNftUpdateAuthSignature}?cluster=devnet`)
-
     // Derive collection metadata pda account
     collectionMetadataAccount = PublicKey.findProgramAddressSync(
       [
@@ -231,9 +285,15 @@ describe("deinference", () => {
   it.only("Initializes the program state pda", async () => {
     
     // Call the initialize function
-    const tx = await program.methods.initialize()
+    // Only the upgrade authority, our wallet on localnet, can initialize the program
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    const tx = await program.methods.initialize(wallet.publicKey)
     .accounts({
       payer: wallet.publicKey,
+      programData,
     })
     .signers([wallet.payer])
     .rpc({commitment: 'confirmed'});
@@ -248,14 +308,12 @@ describe("deinference", () => {
       "tree_state account is not owned by the program"
     );
 
-    assert.strictEqual(
-      programStateData.creator.toString(),
-      "2gUrmvYsLTpXB5VwjP2ZpXD4kY4HWRP89aDzQQ7TKbwh",
-      "unexpected creator");
+    assert.ok(programStateData.admin.equals(wallet.publicKey), "unexpected admin");
+    assert.isNull(programStateData.pendingAdmin);
 
     assert.strictEqual(
       programStateAccountInfo.data.length,
-      46 + 1 * 66, // space = account disc (8) + pubkey (32) + vec size (4) + tree count (2) + max_#_trees * tree info (66)
+      87, // space = account disc (8) + admin (32) + pending admin (33) + tree count (2) + request count (8) + vec size (4), grown by create_tree
       "tree_state account data size is incorrect"
     );
   });
@@ -269,6 +327,7 @@ describe("deinference", () => {
       tree: tree.publicKey,
       treeConfig: tree_config,
       payer: wallet.publicKey,
+      admin: wallet.publicKey,
     }).signers([wallet.payer])
     .rpc({ commitment: 'confirmed'});
 
@@ -292,6 +351,59 @@ describe("deinference", () => {
       1,
       "Unexpected number of merkle trees"
     );
+    // The program state grows by one tree info (66) per tree
+    const grownProgramStateInfo = await provider.connection.getAccountInfo(programStatePda);
+    assert.strictEqual(grownProgramStateInfo.data.length, 87 + 66, "program state was not grown for the new tree");
+  });
+
+  it("Transfers the admin role in two steps", async () => {
+    const newAdmin = Keypair.generate();
+    await confirmTransaction(await program.methods.proposeAdmin(newAdmin.publicKey)
+      .accounts({ admin: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: 'confirmed' }));
+
+    // The current admin stays in charge until the proposal is accepted
+    programStateData = await program.account.programState.fetch(programStatePda);
+    assert.ok(programStateData.admin.equals(wallet.publicKey));
+    assert.ok(programStateData.pendingAdmin.equals(newAdmin.publicKey));
+
+    await confirmTransaction(await program.methods.acceptAdmin()
+      .accounts({ pendingAdmin: newAdmin.publicKey })
+      .signers([newAdmin]).rpc({ commitment: 'confirmed' }));
+    programStateData = await program.account.programState.fetch(programStatePda);
+    assert.ok(programStateData.admin.equals(newAdmin.publicKey));
+    assert.isNull(programStateData.pendingAdmin);
+
+    // Hand the role back to our wallet for the remaining tests
+    await confirmTransaction(await program.methods.proposeAdmin(wallet.publicKey)
+      .accounts({ admin: newAdmin.publicKey })
+      .signers([newAdmin]).rpc({ commitment: 'confirmed' }));
+    await confirmTransaction(await program.methods.acceptAdmin()
+      .accounts({ pendingAdmin: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: 'confirmed' }));
+  });
+
+  it("Updates the protocol configuration within bounds", async () => {
+    const [configPda] = PublicKey.findProgramAddressSync([Buffer.from("config")], program.programId);
+    const config = await program.account.protocolConfig.fetch(configPda);
+    assert.strictEqual(config.initialReputation, 1);
+    assert.strictEqual(config.maxResults, 10);
+    assert.strictEqual(config.maxInputSize, 32);
+    assert.ok(config.treasury.equals(wallet.publicKey));
+
+    try {
+      await program.methods.updateConfig({ ...config, protocolFeeBps: 10_001 })
+        .accounts({ admin: wallet.publicKey })
+        .signers([wallet.payer]).rpc({ commitment: 'confirmed' });
+      assert.fail("an out of bounds protocol fee should be rejected");
+    } catch (err) {
+      assert.strictEqual(err.error?.errorCode?.code, "InvalidConfig");
+    }
+
+    await confirmTransaction(await program.methods.updateConfig({ ...config, maxResults: 16 })
+      .accounts({ admin: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: 'confirmed' }));
+    assert.strictEqual((await program.account.protocolConfig.fetch(configPda)).maxResults, 16);
   });
 
   it("Mints an NFT to an existing merkle tree", async () => {
@@ -303,6 +415,7 @@ describe("deinference", () => {
     .mint(metadata.name, metadata.symbol, metadata.uri, 10)
     .accounts({
       payer: wallet.publicKey,
+      admin: wallet.publicKey,
       tree: tree.publicKey,
       treeAuth: tree_config,
       leafOwner: leafOwner.publicKey
@@ -348,10 +461,11 @@ describe("deinference", () => {
   it("Initializes a new inference task collection", async () => {
     // Call the create_task instruction
     const tx = await program.methods
-      .createTask().accounts({
+      .createTask(taskSchema, taskSettings).accounts({
         collectionMint: collection_mint.publicKey,
-        //metadata: collectionMetadataAccount,
-        payer: wallet.publicKey,
+        collectionMetadata: collectionMetadataAccount,
+        feeMint: null, // fees are paid in lamports
+        payer: wallet.publicKey, // the collection's update authority, becomes the task owner
       })
       .signers([wallet.payer]) 
     .rpc({commitment: 'confirmed'});
@@ -362,20 +476,44 @@ describe("deinference", () => {
     // Fetch the task_data account and assert it was initialized
     const taskDataAccountInfo = await provider.connection.getAccountInfo(taskDataPda);
     const taskDataAccount = await program.account.taskData.fetch(taskDataPda);
-    assert.strictEqual(taskDataAccountInfo.data.length, 330);
+    assert.strictEqual(taskDataAccountInfo.data.length, 193);
     assert.ok(taskDataAccount.collectionMint.equals(new PublicKey(collection_mint.publicKey)));
+    assert.ok(taskDataAccount.owner.equals(wallet.publicKey));
+
+    // Only the task owner can change its settings
+    await program.methods
+      .updateTask({ ...taskSettings, minReputation: 1 }).accounts({
+        owner: wallet.publicKey,
+        collectionMint: collection_mint.publicKey,
+      })
+      .signers([wallet.payer])
+    .rpc({commitment: 'confirmed'});
+    const updatedTask = await program.account.taskData.fetch(taskDataPda);
+    assert.strictEqual(updatedTask.settings.minReputation, 1);
+    assert.strictEqual(updatedTask.settings.commitWindow, null);
+
+    // Hand the collection's update authority to the tree owner so models can be minted into it
+    const updateNftUpdateAuthTx = await updateV1(umi, {
+      mint: collection_mint.publicKey,
+      newUpdateAuthority: tree_owner.toBase58() as UmiPK
+    }).sendAndConfirm(umi);
+
+    const updateNftUpdateAuthSignature = base58.deserialize(updateNftUpdateAuthTx.signature)[0];
+    console.log(`https://explorer.solana.com/tx/${updateNftUpdateAuthSignature}?cluster=devnet`)
+    await confirmTransaction(updateNftUpdateAuthSignature);
   });
 
   it("Mints an NFT to an existing merkle tree and task (collection)", async () => {
-    const signature = nacl.sign.detached(Buffer.from(metadata.uri), wallet.payer.secretKey);
+    const message = weightsMessage(new PublicKey(collection_mint.publicKey), wallet.publicKey, metadata.weightsHash);
+    const signature = nacl.sign.detached(message, wallet.payer.secretKey);
     const ed25519Instruction = Ed25519Program.createInstructionWithPublicKey({
       publicKey: wallet.payer.publicKey.toBytes(),
-      message: Buffer.from(metadata.uri),
+      message,
       signature: signature
     });
 
     const tx = await program.methods
-      .mintToTask(metadata.name, metadata.symbol, metadata.uri, Array.from(signature), 0)
+      .mintToTask(metadata.name, metadata.symbol, metadata.uri, Array.from(metadata.weightsHash), Array.from(signature), 0)
       .accounts({
         treeAuth: tree_config,
         modelOwner: wallet.publicKey,
@@ -393,12 +531,88 @@ describe("deinference", () => {
     const taskData = await program.account.taskData.fetch(taskDataPda);
 
     assert.strictEqual(taskData.modelCount, 1);
-    assert.deepEqual(taskData.models[0].weightsHash, Array.from(anchor.utils.bytes.utf8.encode(metadata.uri)));
+    const modelData = await program.account.modelData.fetch(modelPda(new PublicKey(collection_mint.publicKey), metadata.weightsHash));
+    assert.deepEqual(modelData.weightsHash, Array.from(metadata.weightsHash));
+    assert.ok(modelData.owner.equals(wallet.publicKey));
   
   });
 
+  it("Mints a batch of models from one owner with a single Ed25519 instruction", async () => {
+    const models = [0, 1].map((i) => ({
+      name: `BATCH-NFT-${i}`,
+      symbol: `B${i}`,
+      uri: `https://example.com/models/batch-${i}.json`,
+      weightsHash: createHash("sha256").update(`batch model ${i} weights`).digest()
+    }));
+    const collection = new PublicKey(collection_mint.publicKey);
+    const messages = models.map((model) => weightsMessage(collection, wallet.publicKey, model.weightsHash));
+    const signatures = messages.map((message) => nacl.sign.detached(message, wallet.payer.secretKey));
+    const modelAccounts = models.map((model) => modelPda(collection, model.weightsHash));
+
+    const tx = await program.methods
+      .mintBatchToTask(models.map((model, i) => ({
+        name: model.name,
+        symbol: model.symbol,
+        uri: model.uri,
+        weightsHash: Array.from(model.weightsHash),
+        weightsSignature: Array.from(signatures[i])
+      })), 0)
+      .accounts({
+        treeAuth: tree_config,
+        modelOwner: wallet.publicKey,
+        tree: tree.publicKey,
+        collectionMint: collection_mint.publicKey,
+        collectionMetadata: collectionMetadataAccount,
+        bubblegumSigner: bubblegumSigner,
+        editionAccount:  editionAccount,
+      })
+      .remainingAccounts(modelAccounts.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })))
+      .preInstructions([ed25519BatchInstruction(wallet.publicKey, messages, signatures)])
+      .signers([wallet.payer])
+    .rpc({ commitment: 'confirmed' });
+    await confirmTransaction(tx);
+
+    const taskData = await program.account.taskData.fetch(taskDataPda);
+    assert.strictEqual(taskData.modelCount, 3);
+    for (let i = 0; i < models.length; i++) {
+      const modelData = await program.account.modelData.fetch(modelAccounts[i]);
+      assert.deepEqual(modelData.weightsHash, Array.from(models[i].weightsHash));
+      assert.strictEqual(modelData.leafIndex, programStateData.trees[0].currentIndex + i);
+      assert.ok(modelData.owner.equals(wallet.publicKey));
+    }
+  });
+
+  it("Mints a model whose weights hash is attested by an Ethereum key", async () => {
+    const collection = new PublicKey(collection_mint.publicKey);
+    const secp256k1Instruction = Secp256k1Program.createInstructionWithPrivateKey({
+      privateKey: ethModel.privateKey,
+      message: ethSignedMessage(weightsMessage(collection, wallet.publicKey, ethModel.weightsHash)),
+      instructionIndex: 0
+    });
+    // The instruction carries the address derived from the private key right after its 12 byte header
+    const ethAddress = secp256k1Instruction.data.subarray(12, 32);
+
+    const tx = await program.methods
+      .mintToTaskEth("ETH-NFT", "ETH", "https://example.com/models/eth.json", Array.from(ethModel.weightsHash), Array.from(ethAddress), 0)
+      .accounts({
+        treeAuth: tree_config,
+        modelOwner: wallet.publicKey,
+        tree: tree.publicKey,
+        collectionMint: collection_mint.publicKey,
+        collectionMetadata: collectionMetadataAccount,
+        bubblegumSigner: bubblegumSigner,
+        editionAccount:  editionAccount,
+      }).preInstructions([secp256k1Instruction]).signers([wallet.payer])
+    .rpc({ commitment: 'confirmed' });
+    await confirmTransaction(tx);
+
+    const modelData = await program.account.modelData.fetch(modelPda(collection, ethModel.weightsHash));
+    assert.deepEqual(modelData.identity, { secp256k1: { ethAddress: Array.from(ethAddress) } });
+    assert.ok(modelData.owner.equals(wallet.publicKey));
+  });
+
   it("Retrieves a model from task data and emits an event", async () => {
-    const weightsHash = Array.from(anchor.utils.bytes.utf8.encode(metadata.uri));
+    const weightsHash = Array.from(metadata.weightsHash);
 
     const listener = program.addEventListener("modelRetrieved", (event, slot) => {
       console.log("Event data:", event);
@@ -497,8 +711,11 @@ describe("deinference", () => {
     const serializedData = borsh.serialize(schema, requestData);
     console.log("data length:", serializedData.length);
 
+    // Requests take the next id from the program wide counter
+    request_id = (await program.account.programState.fetch(programStatePda)).requestCount;
+    requestStatePda = requestPda(request_id);
     const listener = program.addEventListener("request", (event, slot) => {
-      assert.strictEqual(event.requestId, 0),
+      assert.ok(event.requestId.eq(request_id)),
       assert.deepEqual(event.taskCollection, new PublicKey(collection_mint.publicKey));
       assert.ok(event.status.pending);
       assert.notOk(event.status.aggregated);
@@ -506,7 +723,8 @@ describe("deinference", () => {
 
     const required_predictions = 1;
 
-    const tx = await program.methods.postRequest(request_id, Buffer.from(serializedData), required_predictions).accounts({
+    const fee = new anchor.BN(0);
+    const tx = await program.methods.postRequest(Buffer.from(serializedData), required_predictions, fee, null).accounts({
       user: wallet.publicKey, // Use our wallet here as the user for simplicity (change later)
       collectionMint: collection_mint.publicKey
     }).signers([wallet.payer]).rpc({commitment: 'confirmed'});
@@ -515,12 +733,13 @@ describe("deinference", () => {
     await program.removeEventListener(listener);
     
     requestStateData = await program.account.inferenceRequest.fetch(requestStatePda);
-    assert.strictEqual(requestStateData.requestId, request_id);
+    assert.ok(requestStateData.requestId.eq(request_id));
+    assert.ok((await program.account.programState.fetch(programStatePda)).requestCount.eq(request_id.addn(1)));
   });
 
   it("Submits a prediction to an inference request with an already registered model", async () => {
 
-    const model_weights = anchor.utils.bytes.utf8.encode(metadata.uri);
+    const model_weights = metadata.weightsHash;
     //const prediction = anchor.utils.bytes.utf8.encode("example_pred");
     const number = 12.25; // Example number
 
@@ -538,11 +757,107 @@ describe("deinference", () => {
     }).signers([wallet.payer]).rpc({commitment: "confirmed"});
     await confirmTransaction(tx);
 
+    // A relayer submits the Ethereum model's prediction, authorized by the signature of its Ethereum key
+    const relayer = Keypair.generate();
+    await confirmTransaction(await connection.requestAirdrop(relayer.publicKey, LAMPORTS_PER_SOL));
+    const secp256k1Instruction = Secp256k1Program.createInstructionWithPrivateKey({
+      privateKey: ethModel.privateKey,
+      message: ethSignedMessage(predictionMessage(request_id, ethModel.weightsHash, buffer)),
+      instructionIndex: 0
+    });
+    const relay_tx = await program.methods.
+      submitPred(request_id, Array.from(ethModel.weightsHash), buffer).
+      accounts({
+        modelOwner: relayer.publicKey,
+        collectionMint: collection_mint.publicKey
+    }).preInstructions([secp256k1Instruction]).signers([relayer]).rpc({commitment: "confirmed"});
+    await confirmTransaction(relay_tx);
+
     requestStateData = await program.account.inferenceRequest.fetch(requestStatePda);
     console.log("request data pred results:", requestStateData.results);
+    // Recorded as the owner's own prediction, the relayer is owed nothing
+    assert.ok(requestStateData.results[requestStateData.results.length - 1].submitter.equals(wallet.publicKey));
 
   });
 
+  it("Rejects predictions while the program or the task is paused", async () => {
+    const submit = () => program.methods
+      .submitPred(request_id, Array.from(metadata.weightsHash), Buffer.alloc(4))
+      .accounts({
+        modelOwner: wallet.publicKey,
+        collectionMint: collection_mint.publicKey
+      }).signers([wallet.payer]).rpc({ commitment: "confirmed" });
+    const setPaused = async (paused: boolean) => confirmTransaction(await program.methods.setPaused(paused)
+      .accounts({ admin: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+    const setTaskPaused = async (paused: boolean) => confirmTransaction(await program.methods.setTaskPaused(paused)
+      .accounts({ admin: wallet.publicKey, collectionMint: collection_mint.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+
+    for (const [pause, unpause, code] of [[setPaused, setPaused, "ProgramPaused"], [setTaskPaused, setTaskPaused, "TaskPaused"]] as const) {
+      await pause(true);
+      try {
+        await submit();
+        assert.fail("submission should be rejected while paused");
+      } catch (err) {
+        assert.strictEqual(err.error?.errorCode?.code, code);
+      }
+      await unpause(false);
+    }
+  });
+
+  it("Commits a prediction and reveals it once the commit window closes", async () => {
+    const updateTask = async (commitWindow: anchor.BN | null) => confirmTransaction(await program.methods
+      .updateTask({ ...taskSettings, minReputation: 1, commitWindow })
+      .accounts({ owner: wallet.publicKey, collectionMint: collection_mint.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+    const expectError = async (promise: Promise<unknown>, code: string) => {
+      try {
+        await promise;
+        assert.fail(`expected ${code}`);
+      } catch (err) {
+        assert.strictEqual(err.error?.errorCode?.code, code);
+      }
+    };
+
+    // Requests posted from now on take commits for their first two seconds
+    await updateTask(new anchor.BN(2));
+    const commit_request_id = (await program.account.programState.fetch(programStatePda)).requestCount;
+    await confirmTransaction(await program.methods
+      .postRequest(Buffer.from(requestStateData.inputData), 1, new anchor.BN(0), null)
+      .accounts({ user: wallet.publicKey, collectionMint: collection_mint.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+
+    const prediction = Buffer.alloc(4);
+    prediction.writeFloatBE(12.5, 0);
+    const salt = randomBytes(32);
+    const commitment = createHash("sha256").update(Buffer.concat([prediction, salt, metadata.weightsHash])).digest();
+
+    await expectError(program.methods.submitPred(commit_request_id, Array.from(metadata.weightsHash), prediction)
+      .accounts({ modelOwner: wallet.publicKey, collectionMint: collection_mint.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }), "CommitRevealRequired");
+
+    await confirmTransaction(await program.methods
+      .commitPred(commit_request_id, Array.from(metadata.weightsHash), Array.from(commitment))
+      .accounts({ modelOwner: wallet.publicKey, collectionMint: collection_mint.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+
+    const reveal = () => program.methods
+      .revealPred(commit_request_id, Array.from(metadata.weightsHash), prediction, Array.from(salt))
+      .accounts({ revealer: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" });
+    await expectError(reveal(), "RevealWindowNotOpen");
+
+    await new Promise((resolve) => setTimeout(resolve, 3_000));
+    await confirmTransaction(await reveal());
+
+    const commitRequest = await program.account.inferenceRequest.fetch(requestPda(commit_request_id));
+    assert.strictEqual(commitRequest.results[0].commitment, null);
+    assert.deepEqual(Buffer.from(commitRequest.results[0].prediction), prediction);
+
+    await updateTask(null);
+  });
+
   it.only("Aggregates the results for an inference request", async () => {
     // Create new nft collection
     const task_account = generateSigner(umi);
@@ -558,30 +873,33 @@ describe("deinference", () => {
     const create_collection_nft_sig = base58.deserialize(create_collection_nft_tx.signature)[0];
     await confirmTransaction(create_collection_nft_sig);
 
-    const update_nft_tx = await updateV1(umi, {
-      mint: task_account.publicKey,
-      newUpdateAuthority: tree_owner.toBase58() as UmiPK
-    }).sendAndConfirm(umi);
-    const update_nft_sig = base58.deserialize(update_nft_tx.signature)[0];
-    await confirmTransaction(update_nft_sig);
+    const collection_nft_metatdata_acc = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata", "utf8"),
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(),
+        new PublicKey(task_account.publicKey).toBuffer(),
+      ],
+      new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
+    )[0];
 
+    // The task is created while our wallet is still the collection's update authority
     const init_task_tx = await program.methods
-      .createTask().accounts({
+      .createTask(taskSchema, taskSettings).accounts({
         collectionMint: task_account.publicKey,
+        collectionMetadata: collection_nft_metatdata_acc,
+        feeMint: null,
         payer: wallet.publicKey,
       })
       .signers([wallet.payer]) 
     .rpc({commitment: 'confirmed'});
     await confirmTransaction(init_task_tx);
 
-    const collection_nft_metatdata_acc = PublicKey.findProgramAddressSync(
-      [
-        Buffer.from("metadata", "utf8"),
-        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(),
-        new PublicKey(task_account.publicKey).toBuffer(),
-      ],
-      new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
-    )[0];
+    const update_nft_tx = await updateV1(umi, {
+      mint: task_account.publicKey,
+      newUpdateAuthority: tree_owner.toBase58() as UmiPK
+    }).sendAndConfirm(umi);
+    const update_nft_sig = base58.deserialize(update_nft_tx.signature)[0];
+    await confirmTransaction(update_nft_sig);
 
     const collection_nft_edition_acc = PublicKey.findProgramAddressSync(
       [
@@ -628,36 +946,43 @@ describe("deinference", () => {
     });
     
     const serializedData = borsh.serialize(schema, requestData);
-    const request_id = 2
+    const request_id = (await program.account.programState.fetch(programStatePda)).requestCount;
 
     const required_predictions = 3
-    const inference_request_tx = await program.methods.postRequest(request_id, Buffer.from(serializedData), required_predictions).accounts({
+    const fee = new anchor.BN(LAMPORTS_PER_SOL / 10);
+    const inference_request_tx = await program.methods.postRequest(Buffer.from(serializedData), required_predictions, fee, null).accounts({
       user: wallet.publicKey,
       collectionMint: task_account.publicKey
     }).signers([wallet.payer]).rpc({commitment: 'confirmed'});
     await confirmTransaction(inference_request_tx);
 
     // Setup  models
+    const modelOwners: PublicKey[] = [];
+    const modelAccounts: PublicKey[] = [];
     for (let i = 0; i < 3; i++) {
       const account = Keypair.generate();
+      modelOwners.push(account.publicKey);
       const airdrop_tx = await connection.requestAirdrop(account.publicKey, LAMPORTS_PER_SOL);
       await confirmTransaction(airdrop_tx);
 
       const model_metadata = {
-        uri: `${i.toString().padStart(32, '0')}`, // Pad `i` with leading zeros to make it 32 bytes
+        uri: `https://example.com/models/${i}.json`,
         name: `TEST-NFT-${i}`,
-        symbol: `${i}`
+        symbol: `${i}`,
+        weightsHash: createHash("sha256").update(`model ${i} weights`).digest()
       };
+      modelAccounts.push(modelPda(new PublicKey(task_account.publicKey), model_metadata.weightsHash));
     
-      const signature = nacl.sign.detached(Buffer.from(model_metadata.uri), account.secretKey);
+      const message = weightsMessage(new PublicKey(task_account.publicKey), account.publicKey, model_metadata.weightsHash);
+      const signature = nacl.sign.detached(message, account.secretKey);
       const ed25519Instruction = Ed25519Program.createInstructionWithPublicKey({
         publicKey: account.publicKey.toBytes(),
-        message: Buffer.from(model_metadata.uri),
+        message,
         signature: signature
       });
 
       const mint_tx = await program.methods
-        .mintToTask(model_metadata.name, model_metadata.symbol, model_metadata.uri, Array.from(signature), 0)
+        .mintToTask(model_metadata.name, model_metadata.symbol, model_metadata.uri, Array.from(model_metadata.weightsHash), Array.from(signature), 0)
         .accounts({
           payer: account.publicKey,
           treeAuth: tree_config,
@@ -676,29 +1001,63 @@ describe("deinference", () => {
       predictionBuffer.writeFloatBE(predictionValue, 0);
       console.log("PREDICTION BUFFER:", predictionBuffer.toString())
 
+      // Only the model's owner may submit on its behalf
+      try {
+        await program.methods
+          .submitPred(request_id, Array.from(model_metadata.weightsHash), predictionBuffer)
+          .accounts({
+            modelOwner: wallet.publicKey,
+            collectionMint: task_account.publicKey,
+          })
+          .signers([wallet.payer])
+        .rpc({ commitment: "confirmed" });
+        assert.fail("submission by a non-owner should be rejected");
+      } catch (err) {
+        assert.strictEqual(err.error?.errorCode?.code, "UnauthorizedSubmitter");
+      }
+
+      // The first model delegates submission to our wallet as its operator, keeping 75% of its payout
+      const submitter = i === 0 ? wallet.payer : account;
+      if (i === 0) {
+        const operator_tx = await program.methods
+          .setModelOperator(Array.from(model_metadata.weightsHash), wallet.publicKey, 2_500)
+          .accounts({
+            modelOwner: account.publicKey,
+            collectionMint: task_account.publicKey,
+          })
+          .signers([account])
+        .rpc({ commitment: "confirmed" });
+        await confirmTransaction(operator_tx);
+      }
+
       const predict_tx = await program.methods
         .submitPred(
           request_id,
-          Array.from(Buffer.from(model_metadata.uri)),
+          Array.from(model_metadata.weightsHash),
           predictionBuffer
         )
         .accounts({
-          modelOwner: wallet.publicKey,
+          modelOwner: submitter.publicKey,
           collectionMint: task_account.publicKey,
         })
-        .signers([wallet.payer])
+        .signers([submitter])
       .rpc({ commitment: "confirmed" });
       await confirmTransaction(predict_tx);
     }
 
     console.log("Models successfully setup");
 
-    const algorithm = { weightedMedian: {} };
+    // Aggregated with the task's default algorithm and reputation policy
     const tx = await program.methods.
-      aggregate(request_id, algorithm).
+      aggregate(request_id, null).
       accounts({
-        user: wallet.publicKey
-      }).signers([wallet.payer]).rpc({commitment: "confirmed"});
+        user: wallet.publicKey,
+        requester: wallet.publicKey,
+        treasury: wallet.publicKey, // the admin until a treasury is configured
+      })
+      // The models being scored, then each model owner for their share of the fee and our wallet for the first model's operator share
+      .remainingAccounts([...modelAccounts, ...modelOwners, wallet.publicKey].map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })))
+      .signers([wallet.payer]).rpc({commitment: "confirmed"});
     await confirmTransaction(tx);
 
     const txDetails = await program.provider.connection.getTransaction(tx, {
@@ -709,5 +1068,20 @@ describe("deinference", () => {
     const logs = txDetails?.meta?.logMessages || null;
     console.log(logs);
 
+    const aggregateRequestPda = requestPda(request_id);
+    const aggregateRequestData = await program.account.inferenceRequest.fetch(aggregateRequestPda);
+    assert.ok(aggregateRequestData.status.aggregated);
+    assert.strictEqual(Buffer.from(aggregateRequestData.result).readFloatBE(), 13.25);
+
+    // Median model is rewarded, the outer models sit in the neutral band
+    const aggregateModels = await program.account.modelData.fetchMultiple(modelAccounts);
+    assert.deepEqual(aggregateModels.map((model) => model.reputation), [1, 2, 1]);
+
+    // Once aggregated, the requester can close the request to reclaim its rent
+    await confirmTransaction(await program.methods.closeRequest(request_id)
+      .accounts({ user: wallet.publicKey })
+      .signers([wallet.payer]).rpc({ commitment: "confirmed" }));
+    assert.isNull(await provider.connection.getAccountInfo(aggregateRequestPda));
+
   });
 });